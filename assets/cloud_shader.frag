@@ -0,0 +1,78 @@
+#version 450
+
+#define octaves 5
+
+layout(location = 0) in vec3 v_local;
+layout(location = 1) in vec3 v_position;
+layout(location = 2) in vec3 v_center;
+
+layout(location = 0) out vec4 o_Target;
+
+layout(set = 1, binding = 1) uniform CloudMaterial_color {
+    vec4 color;
+};
+layout(set = 1, binding = 2) uniform CloudMaterial_coverage {
+    float coverage;
+};
+layout(set = 1, binding = 3) uniform CloudMaterial_speed {
+    float speed;
+};
+layout(set = 1, binding = 4) uniform CloudMaterial_time {
+    float time;
+};
+layout(set = 1, binding = 5) uniform CloudMaterial_sun_position {
+    vec4 sun_position;
+};
+
+#include "noise.glsl"
+
+float fbm(vec3 p) {
+    float total = 0.0;
+    float amplitude = 0.5;
+    for (int i = 0; i < octaves; i++) {
+        total += amplitude * snoise(p);
+        p *= 2.0;
+        amplitude *= 0.5;
+    }
+    return total;
+}
+
+// zonal wind: bands that change direction with latitude, spinning around the planet's axis.
+// Left running, the shear between bands would grow without end and smear the clouds into thin
+// stripes, so each band is only carried max_shift radians before the flow starts over. Two
+// flows half a cycle apart are cross-faded so the restart never shows
+#define max_shift 0.5
+
+// noise at dir carried `offset` of a cycle ahead, and its weight in the cross-fade, which is
+// zero at the restart. Every cycle reads another part of the noise so it doesn't repeat
+vec2 wind_noise(vec3 dir, float lat, float cycles, float offset) {
+    float cycle = cycles + offset;
+    float phase = fract(cycle);
+    float angle = max_shift * phase * cos(3.0 * lat);
+    float c = cos(angle);
+    float s = sin(angle);
+    vec3 p = vec3(c * dir.x - s * dir.z, dir.y, s * dir.x + c * dir.z);
+
+    // drift through the noise slowly so the clouds also change shape
+    vec3 drift = vec3(0.0, 0.0, time * speed * 0.5);
+    vec3 jump = (floor(cycle) + offset) * vec3(5.3, 0.0, 7.1);
+    return vec2(fbm(p * 4.0 + drift + jump), 1.0 - abs(2.0 * phase - 1.0));
+}
+
+void main() {
+    vec3 dir = normalize(v_local);
+    float lat = asin(dir.y);
+
+    float cycles = time * speed / max_shift;
+    vec2 a = wind_noise(dir, lat, cycles, 0.0);
+    vec2 b = wind_noise(dir, lat, cycles, 0.5);
+    // blending two unrelated noises flattens them, scale back up so coverage stays the same
+    float n = (a.x * a.y + b.x * b.y) / sqrt(a.y * a.y + b.y * b.y);
+    n = n * 0.5 + 0.5;
+    float density = smoothstep(1.0 - coverage, 1.0 - coverage + 0.25, n);
+
+    vec3 normal = normalize(v_position - v_center);
+    float light = max(dot(normal, normalize(sun_position.xyz - v_position)), 0.05);
+
+    o_Target = vec4(color.rgb * light, color.a * density);
+}
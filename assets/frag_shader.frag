@@ -11,6 +11,11 @@ layout(location = 1) in float v_height;
 layout(location = 2) in vec4 v_color;
 layout(location = 3) in vec3 v_position;
 layout(location = 4) in vec3 v_center;
+layout(location = 5) in vec3 v_normal;
+layout(location = 6) in vec4 v_tangent;
+layout(location = 7) flat in uint v_biome;
+layout(location = 8) in vec3 v_local;
+layout(location = 9) in vec3 v_local_normal;
 
 layout(location = 0) out vec4 o_Target;
 
@@ -30,22 +35,94 @@ layout(set = 1, binding = 5) uniform StellarMaterial_camera_pos {
 layout(set = 1, binding = 2) uniform texture2D StellarMaterial_texture;
 layout(set = 1, binding = 3) uniform sampler StellarMaterial_texture_sampler;
 # endif
+# ifdef STELLARMATERIAL_NORMAL_MAP
+layout(set = 1, binding = 6) uniform texture2D StellarMaterial_normal_map;
+layout(set = 1, binding = 7) uniform sampler StellarMaterial_normal_map_sampler;
+# endif
+# ifdef STELLARMATERIAL_BIOME_TEXTURES
+layout(set = 1, binding = 8) uniform texture2D StellarMaterial_biome_textures;
+layout(set = 1, binding = 9) uniform sampler StellarMaterial_biome_textures_sampler;
+# endif
+layout(set = 1, binding = 10) uniform StellarMaterial_biome_layers {
+    float biome_layers;
+};
+layout(set = 1, binding = 11) uniform StellarMaterial_texture_scale {
+    float texture_scale;
+};
+# ifdef STELLARMATERIAL_CUBEMAP
+layout(set = 1, binding = 12) uniform texture2D StellarMaterial_cubemap;
+layout(set = 1, binding = 13) uniform sampler StellarMaterial_cubemap_sampler;
+# endif
+layout(set = 1, binding = 14) uniform StellarMaterial_sun_position {
+    vec4 sun_position;
+};
 
-vec2 rsi(vec3 r0, vec3 rd, float sr) {
-    // ray-sphere intersection that assumes
-    // the sphere is centered at the origin.
-    // No intersection when result.x > result.y
-    float a = dot(rd, rd);
-    float b = 2.0 * dot(rd, r0);
-    float c = dot(r0, r0) - (sr * sr);
-    float d = (b*b) - 4.0*a*c;
-    if (d < 0.0) return vec2(1e5,-1e5);
-    return vec2(
-        (-b - sqrt(d))/(2.0*a),
-        (-b + sqrt(d))/(2.0*a)
-    );
+#include "rsi.glsl"
+
+
+# ifdef STELLARMATERIAL_BIOME_TEXTURES
+// the biome textures are packed side by side instead of in a texture array, which bevy's
+// Texture can't hold. layer picks the tile, which is inset by half a texel of the mip level
+// in use so filtering doesn't pull in the neighbouring biome. The gradients come from the
+// unwrapped uvs so the repeat doesn't drop to the smallest mip either
+vec4 sample_biome(vec2 uv, float layer) {
+    vec2 scale = vec2(1.0 / biome_layers, 1.0);
+    vec2 dx = dFdx(uv) * scale;
+    vec2 dy = dFdy(uv) * scale;
+    vec2 size = vec2(textureSize(
+        sampler2D(StellarMaterial_biome_textures, StellarMaterial_biome_textures_sampler), 0));
+    float texels = max(length(dx * size), length(dy * size));
+    vec2 inset = 0.5 * max(texels, 1.0) / (size * scale);
+    vec2 tile = clamp(fract(uv), inset, 1.0 - inset);
+    return textureGrad(
+        sampler2D(StellarMaterial_biome_textures, StellarMaterial_biome_textures_sampler),
+        vec2((tile.x + layer) * scale.x, tile.y), dx, dy);
 }
+# endif
 
+// what the triplanar mode projects: the biome's texture, or the plain texture without them
+vec4 surface_texture(vec2 uv, float layer) {
+# if defined(STELLARMATERIAL_BIOME_TEXTURES)
+    return sample_biome(uv, layer);
+# elif defined(STELLARMATERIAL_TEXTURE)
+    return texture(sampler2D(StellarMaterial_texture, StellarMaterial_texture_sampler), uv);
+# else
+    return vec4(1.0);
+# endif
+}
+
+// n is the surface normal, so cliffs and mountain sides get the side projections
+vec4 triplanar(vec3 p, vec3 n, float layer) {
+    // sharpen the blend so the three projections don't smear into each other
+    vec3 w = pow(abs(n), vec3(4.0));
+    w /= (w.x + w.y + w.z);
+    return surface_texture(p.yz * texture_scale, layer) * w.x
+        + surface_texture(p.xz * texture_scale, layer) * w.y
+        + surface_texture(p.xy * texture_scale, layer) * w.z;
+}
+
+# ifdef STELLARMATERIAL_CUBEMAP
+// same face layout and orientation as a GL cubemap, with the faces packed side by side
+vec4 cubemap(vec3 d) {
+    vec3 a = abs(d);
+    float face;
+    vec2 uv;
+    if (a.x >= a.y && a.x >= a.z) {
+        face = d.x > 0.0 ? 0.0 : 1.0;
+        uv = vec2(d.x > 0.0 ? -d.z : d.z, -d.y) / a.x;
+    } else if (a.y >= a.z) {
+        face = d.y > 0.0 ? 2.0 : 3.0;
+        uv = vec2(d.x, d.y > 0.0 ? d.z : -d.z) / a.y;
+    } else {
+        face = d.z > 0.0 ? 4.0 : 5.0;
+        uv = vec2(d.z > 0.0 ? d.x : -d.x, -d.y) / a.z;
+    }
+    uv = uv * 0.5 + 0.5;
+    return texture(
+        sampler2D(StellarMaterial_cubemap, StellarMaterial_cubemap_sampler),
+        vec2((uv.x + face) / 6.0, uv.y));
+}
+# endif
 
 vec3 atmosphere(vec3 r, vec3 r0, vec3 pSun, float iSun, float rPlanet, float rAtmos, vec3 kRlh, float kMie, float shRlh, float shMie, float g) {
     // Normalize the sun and view directions.
@@ -146,10 +223,33 @@ void main() {
 
     vec3 cam_pos = vec3(camera_mat[3]);
 
+    vec4 albedo = v_color;
+# ifdef STELLARMATERIAL_TRIPLANAR
+    // biome layers follow the order of the `Biome` enum: ocean floor, then land
+    float layer = float(v_biome);
+    albedo *= triplanar(v_local, normalize(v_local_normal), layer);
+# endif
+# ifdef STELLARMATERIAL_CUBEMAP
+    albedo *= cubemap(normalize(v_local));
+# endif
+
+    float detail_light = 1.0;
+# ifdef STELLARMATERIAL_NORMAL_MAP
+    // tangent space detail normal, bitangent rebuilt the MikkTSpace way
+    vec3 n = normalize(v_normal);
+    vec3 t = normalize(v_tangent.xyz);
+    vec3 b = cross(n, t) * v_tangent.w;
+    vec3 detail = texture(
+        sampler2D(StellarMaterial_normal_map, StellarMaterial_normal_map_sampler),
+        v_Uv).xyz * 2.0 - 1.0;
+    vec3 normal = normalize(mat3(t, b, n) * detail);
+    detail_light = max(dot(normal, normalize(sun_position.xyz - v_position)), 0.1);
+# endif
+
     vec3 atmo_color = atmosphere(
         normalize(v_position - cam_pos), // normalized direction from camera to vertex
         cam_pos, // camera position
-        vec3(0.0, 0.0, 100000.0), // position of the sun
+        sun_position.xyz, // position of the sun
         202.0, // intensity of the sun
         40000, // planet radius
         45000, // atmo radius
@@ -164,5 +264,5 @@ void main() {
 
     // o_Target = v_color * acolor;
     // o_Target = acolor;
-    o_Target = v_color * vec4(atmo_color, 1.0);
+    o_Target = albedo * vec4(atmo_color * detail_light, 1.0);
 }
@@ -0,0 +1,67 @@
+#version 450
+
+layout(location = 0) in vec3 v_local;
+layout(location = 1) in vec3 v_position;
+layout(location = 2) in vec3 v_center;
+layout(location = 3) in float v_depth;
+
+layout(location = 0) out vec4 o_Target;
+
+layout(set = 1, binding = 1) uniform OceanMaterial_shallow_color {
+    vec4 shallow_color;
+};
+layout(set = 1, binding = 2) uniform OceanMaterial_deep_color {
+    vec4 deep_color;
+};
+layout(set = 1, binding = 3) uniform OceanMaterial_depth_scale {
+    float depth_scale;
+};
+layout(set = 1, binding = 4) uniform OceanMaterial_wave_strength {
+    float wave_strength;
+};
+layout(set = 1, binding = 5) uniform OceanMaterial_time {
+    float time;
+};
+layout(set = 1, binding = 6) uniform OceanMaterial_sun_position {
+    vec4 sun_position;
+};
+layout(set = 1, binding = 7) uniform OceanMaterial_camera_pos {
+    mat4 camera_mat;
+};
+
+#include "noise.glsl"
+
+vec3 wave_normal(vec3 normal, vec3 p) {
+    // three decorrelated noise samples nudge the normal, then the radial part is dropped
+    // so the waves only tilt the surface
+    vec3 offset = vec3(
+        snoise(p + vec3(time * 0.3, 0.0, 0.0)),
+        snoise(p + vec3(31.4, time * 0.3, 0.0)),
+        snoise(p + vec3(0.0, -17.1, time * 0.3))
+    );
+    offset -= normal * dot(offset, normal);
+    return normalize(normal + offset * wave_strength);
+}
+
+void main() {
+    vec3 normal = normalize(v_position - v_center);
+    normal = wave_normal(normal, normalize(v_local) * 400.0);
+
+    vec3 cam_pos = vec3(camera_mat[3]);
+    vec3 to_camera = normalize(cam_pos - v_position);
+    vec3 to_sun = normalize(sun_position.xyz - v_position);
+
+    // deeper water is darker and more opaque
+    float depth = clamp(v_depth / depth_scale, 0.0, 1.0);
+    vec4 water = mix(shallow_color, deep_color, depth);
+
+    // Schlick's approximation, water reflects a lot more at grazing angles
+    float fresnel = 0.02 + 0.98 * pow(1.0 - max(dot(normal, to_camera), 0.0), 5.0);
+
+    float diffuse = max(dot(normal, to_sun), 0.05);
+    vec3 half_dir = normalize(to_sun + to_camera);
+    float glint = pow(max(dot(normal, half_dir), 0.0), 200.0) * step(0.0, dot(normal, to_sun));
+
+    vec3 color = water.rgb * diffuse + vec3(fresnel * 0.3 * diffuse) + vec3(glint);
+    o_Target = vec4(color, clamp(water.a + fresnel + glint, 0.0, 1.0));
+}
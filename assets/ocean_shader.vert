@@ -0,0 +1,25 @@
+#version 450
+layout(location = 0) in vec3 Vertex_Position;
+layout(location = 1) in vec3 Vertex_Normal;
+layout(location = 2) in vec2 Vertex_Uv;
+layout(location = 4) in float OceanVertex_Depth;
+
+layout(location = 0) out vec3 v_local; // position on the ocean sphere relative to the planet
+layout(location = 1) out vec3 v_position; // world position of the vertex
+layout(location = 2) out vec3 v_center; // center of the planet
+layout(location = 3) out float v_depth; // depth of the water above the terrain
+
+layout(set = 0, binding = 0) uniform Camera {
+    mat4 ViewProj;
+};
+layout(set = 1, binding = 0) uniform Transform {
+    mat4 Model;
+};
+
+void main() {
+    v_local = Vertex_Position;
+    v_center = vec3(Model[3]);
+    v_position = vec3(Model * vec4(Vertex_Position, 1.0));
+    v_depth = OceanVertex_Depth;
+    gl_Position = ViewProj * Model * vec4(Vertex_Position, 1.0);
+}
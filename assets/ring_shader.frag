@@ -0,0 +1,32 @@
+#version 450
+
+layout(location = 0) in vec2 v_Uv;
+layout(location = 1) in vec3 v_position;
+layout(location = 2) in vec3 v_center;
+
+layout(location = 0) out vec4 o_Target;
+
+layout(set = 1, binding = 1) uniform texture2D RingMaterial_texture;
+layout(set = 1, binding = 2) uniform sampler RingMaterial_texture_sampler;
+layout(set = 1, binding = 3) uniform RingMaterial_planet_radius {
+    float planet_radius;
+};
+layout(set = 1, binding = 4) uniform RingMaterial_sun_position {
+    vec4 sun_position;
+};
+
+#include "rsi.glsl"
+
+void main() {
+    // radial density and colour both come from the 1-D noise texture
+    vec4 ring = texture(
+        sampler2D(RingMaterial_texture, RingMaterial_texture_sampler),
+        vec2(v_Uv.x, 0.5));
+
+    // darken the part of the rings that sits in the planet's shadow
+    vec3 to_sun = normalize(sun_position.xyz - v_position);
+    vec2 hit = rsi(v_position - v_center, to_sun, planet_radius);
+    float shadow = (hit.x <= hit.y && hit.y > 0.0) ? 0.2 : 1.0;
+
+    o_Target = vec4(ring.rgb * shadow, ring.a);
+}
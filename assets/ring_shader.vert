@@ -0,0 +1,23 @@
+#version 450
+layout(location = 0) in vec3 Vertex_Position;
+layout(location = 1) in vec3 Vertex_Normal;
+layout(location = 2) in vec2 Vertex_Uv;
+layout(location = 3) in vec4 Vertex_Color;
+
+layout(location = 0) out vec2 v_Uv; // x is radial, 0 at the inner edge
+layout(location = 1) out vec3 v_position; // world position of the vertex
+layout(location = 2) out vec3 v_center; // center of the planet the rings belong to
+
+layout(set = 0, binding = 0) uniform Camera {
+    mat4 ViewProj;
+};
+layout(set = 1, binding = 0) uniform Transform {
+    mat4 Model;
+};
+
+void main() {
+    v_Uv = Vertex_Uv;
+    v_center = vec3(Model[3]);
+    v_position = vec3(Model * vec4(Vertex_Position, 1.0));
+    gl_Position = ViewProj * Model * vec4(Vertex_Position, 1.0);
+}
@@ -0,0 +1,54 @@
+#version 450
+layout(location = 0) in vec3 Vertex_Position;
+layout(location = 1) in vec3 Vertex_Normal;
+layout(location = 2) in vec2 Vertex_Uv;
+layout(location = 3) in vec4 Vertex_Color;
+layout(location = 4) in vec4 ScatterVertex_Tangent;
+layout(location = 5) in vec4 I_ScatterInstance_Model0; // model matrix on the planet, one column each
+layout(location = 6) in vec4 I_ScatterInstance_Model1;
+layout(location = 7) in vec4 I_ScatterInstance_Model2;
+layout(location = 8) in vec4 I_ScatterInstance_Model3;
+layout(location = 9) in vec4 I_ScatterInstance_Color;
+
+// the same as vert_shader.vert, so the StellarMaterial fragment shader can be reused
+layout(location = 0) out vec2 v_Uv;
+layout(location = 1) out float v_height;
+layout(location = 2) out vec4 v_color;
+layout(location = 3) out vec3 v_position;
+layout(location = 4) out vec3 v_center;
+layout(location = 5) out vec3 v_normal;
+layout(location = 6) out vec4 v_tangent;
+layout(location = 7) flat out uint v_biome;
+layout(location = 8) out vec3 v_local; // in planet space, where the planet projects its textures
+layout(location = 9) out vec3 v_local_normal;
+
+layout(set = 0, binding = 0) uniform Camera {
+    mat4 ViewProj;
+};
+layout(set = 1, binding = 0) uniform Transform {
+    mat4 Model;
+};
+
+void main() {
+    mat4 instance = mat4(
+        I_ScatterInstance_Model0,
+        I_ScatterInstance_Model1,
+        I_ScatterInstance_Model2,
+        I_ScatterInstance_Model3
+    );
+    mat4 model = Model * instance;
+    vec4 position = model * vec4(Vertex_Position, 1.0);
+    v_local = vec3(instance * vec4(Vertex_Position, 1.0));
+    v_local_normal = mat3(instance) * Vertex_Normal;
+
+    v_Uv = Vertex_Uv;
+    v_center = vec3(Model[3]);
+    v_position = position.xyz;
+    v_height = distance(v_center, v_position);
+    v_color = Vertex_Color * I_ScatterInstance_Color;
+    // only uniform scaling, so the model matrix keeps normals perpendicular
+    v_normal = mat3(model) * Vertex_Normal;
+    v_tangent = vec4(mat3(model) * ScatterVertex_Tangent.xyz, ScatterVertex_Tangent.w);
+    v_biome = 0;
+    gl_Position = ViewProj * position;
+}
@@ -3,12 +3,19 @@ layout(location = 0) in vec3 Vertex_Position;
 layout(location = 1) in vec3 Vertex_Normal;
 layout(location = 2) in vec2 Vertex_Uv;
 layout(location = 3) in vec4 Vertex_Color;
+layout(location = 4) in vec4 PlanetVertex_Tangent;
+layout(location = 5) in uint PlanetVertex_Biome;
 
 layout(location = 0) out vec2 v_Uv; // uv value.....
 layout(location = 1) out float v_height; // distance from position to center
 layout(location = 2) out vec4 v_color; // color of the vertex
 layout(location = 3) out vec3 v_position; // position of the vertex
 layout(location = 4) out vec3 v_center; // center of the mesh
+layout(location = 5) out vec3 v_normal; // world space normal
+layout(location = 6) out vec4 v_tangent; // world space tangent, w is the handedness
+layout(location = 7) flat out uint v_biome; // `Biome` of the vertex, zero on meshes that aren't planets
+layout(location = 8) out vec3 v_local; // position in the mesh's own space, projected textures stay put when it turns
+layout(location = 9) out vec3 v_local_normal; // normal in the same space as v_local
 
 layout(set = 0, binding = 0) uniform Camera {
     mat4 ViewProj;
@@ -27,9 +34,14 @@ void main() {
     v_Uv = Vertex_Uv;
     vec3 center = vec3(Model[3]);
     // vec3 center = vec3(Model[3][0], Model[3][1], Model[3][2]);
-    v_height = distance(center, Vertex_Position);
     v_color = Vertex_Color;
     v_center = center;
-    v_position = Vertex_Position + center;
+    v_position = vec3(Model * vec4(Vertex_Position, 1.0));
+    v_height = distance(center, v_position);
+    v_local = Vertex_Position;
+    v_local_normal = Vertex_Normal;
+    v_normal = mat3(Model) * Vertex_Normal;
+    v_tangent = vec4(mat3(Model) * PlanetVertex_Tangent.xyz, PlanetVertex_Tangent.w);
+    v_biome = PlanetVertex_Biome;
     gl_Position = ViewProj * Model * vec4(Vertex_Position, 1.0);
 }
\ No newline at end of file
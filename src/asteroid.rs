@@ -0,0 +1,139 @@
+use crate::rng::Rng;
+use bevy::{
+    math::{vec3, Vec3},
+    render::mesh::{shape, Mesh, VertexAttribute},
+};
+use noise::*;
+
+pub struct AsteroidConfig {
+    /// radius of the longest axis
+    pub radius: f32,
+    /// keep this low, asteroids are meant to be cheap
+    pub subdivisions: usize,
+    /// how much each axis can be squashed (0.0 keeps a sphere), the longest one is then
+    /// stretched back to `radius`
+    pub elongation: f32,
+    /// amplitude of the low frequency lumps, relative to the radius
+    pub roughness: f32,
+    pub frequency: f64,
+    pub craters: usize,
+    /// min and max crater radius, measured on the unit sphere
+    pub crater_size: (f32, f32),
+    pub crater_depth: f32,
+}
+
+impl Default for AsteroidConfig {
+    fn default() -> Self {
+        Self {
+            radius: 1000.0,
+            subdivisions: 4,
+            elongation: 0.6,
+            roughness: 0.25,
+            frequency: 1.2,
+            craters: 12,
+            crater_size: (0.1, 0.45),
+            crater_depth: 0.08,
+        }
+    }
+}
+
+struct Crater {
+    center: Vec3,
+    radius: f32,
+    depth: f32,
+}
+
+impl Crater {
+    // bowl inside the crater plus a raised rim that fades out past the edge
+    fn height(&self, dir: Vec3) -> f32 {
+        let t = (dir - self.center).length() / self.radius;
+        let bowl = if t < 1.0 { t * t - 1.0 } else { 0.0 };
+        let rim = (1.0 - ((t - 1.0) / 0.4).powi(2)).max(0.0) * 0.25;
+        self.depth * (bowl + rim)
+    }
+}
+
+/// Builds potato shaped asteroids. Every variant is derived from `seed`, so the same
+/// seed and variant index always give back the same mesh.
+pub struct AsteroidGenerator {
+    pub seed: u32,
+    pub config: AsteroidConfig,
+}
+
+impl AsteroidGenerator {
+    pub fn new(seed: u32, config: AsteroidConfig) -> Self {
+        Self { seed, config }
+    }
+
+    pub fn generate(&self, variant: u32) -> Mesh {
+        let config = &self.config;
+        let mut rng = Rng::new(((self.seed as u64) << 32) | variant as u64);
+
+        // squash every axis, so the long one points somewhere different on each variant
+        let scale = vec3(
+            rng.range(1.0 - config.elongation, 1.0),
+            rng.range(1.0 - config.elongation, 1.0),
+            rng.range(1.0 - config.elongation, 1.0),
+        );
+        let scale = scale / scale.x().max(scale.y()).max(scale.z());
+        let lumps = Fbm::new()
+            .set_seed(rng.next_u32())
+            .set_octaves(3)
+            .set_frequency(config.frequency);
+        let craters = (0..config.craters)
+            .map(|_| Crater {
+                center: rng.unit_vector(),
+                radius: rng.range(config.crater_size.0, config.crater_size.1),
+                depth: config.crater_depth * rng.range(0.5, 1.0),
+            })
+            .collect::<Vec<_>>();
+
+        let mut mesh = Mesh::from(shape::Icosphere {
+            radius: 1.0,
+            subdivisions: config.subdivisions,
+            split_seam: false,
+        });
+        let indices = mesh.indices.clone().unwrap_or_default();
+
+        let positions = mesh
+            .attribute_mut::<[f32; 3]>(VertexAttribute::POSITION)
+            .unwrap();
+        for verts in positions.iter_mut() {
+            let dir = Vec3::from(*verts);
+            let n = lumps.get([dir.x() as f64, dir.y() as f64, dir.z() as f64]) as f32;
+            let mut height = 1.0 + n * config.roughness;
+            for crater in craters.iter() {
+                height += crater.height(dir);
+            }
+            *verts = (dir * scale * height * config.radius).into();
+        }
+        let normals = smooth_normals(positions, &indices);
+        mesh.insert_attribute(VertexAttribute::normal(normals));
+        let shade = rng.range(0.3, 0.5);
+        for verts in mesh
+            .attribute_mut::<[f32; 4]>(VertexAttribute::COLOR)
+            .unwrap()
+        {
+            *verts = [shade, shade * 0.95, shade * 0.9, 1.0];
+        }
+        mesh
+    }
+
+    pub fn generate_many(&self, count: u32) -> Vec<Mesh> {
+        (0..count).map(|variant| self.generate(variant)).collect()
+    }
+}
+
+/// Area weighted vertex normals, expects counter-clockwise triangles.
+pub fn smooth_normals(positions: &[[f32; 3]], indices: &[u32]) -> Vec<[f32; 3]> {
+    let mut normals = vec![Vec3::zero(); positions.len()];
+    for tri in indices.chunks_exact(3) {
+        let (a, b, c) = (tri[0] as usize, tri[1] as usize, tri[2] as usize);
+        let pa = Vec3::from(positions[a]);
+        let face = (Vec3::from(positions[b]) - pa).cross(Vec3::from(positions[c]) - pa);
+        normals[a] += face;
+        normals[b] += face;
+        normals[c] += face;
+    }
+    normals.into_iter().map(|n| n.normalize().into()).collect()
+}
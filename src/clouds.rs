@@ -0,0 +1,116 @@
+use crate::{planet::PlanetConfig, shaders::glsl};
+use bevy::{
+    prelude::*,
+    render::{
+        pipeline::{DynamicBinding, PipelineDescriptor, PipelineSpecialization, RenderPipeline},
+        render_graph::{base, AssetRenderResourcesNode, RenderGraph},
+        renderer::RenderResources,
+        shader::{ShaderStage, ShaderStages},
+    },
+};
+
+#[derive(RenderResources)]
+pub struct CloudMaterial {
+    pub color: Color,
+    pub coverage: f32,
+    pub speed: f32,
+    pub time: f32,
+    pub sun_position: Vec4,
+}
+
+impl CloudMaterial {
+    pub fn new(config: &PlanetConfig, sun_position: Vec4) -> Self {
+        Self {
+            color: config.clouds.color,
+            coverage: config.clouds.coverage,
+            speed: config.clouds.speed,
+            time: 0.0,
+            sun_position,
+        }
+    }
+}
+
+/// Sets up the cloud shaders and render graph node, returning the pipelines to put on cloud shells.
+pub fn cloud_pipelines(
+    pipelines: &mut Assets<PipelineDescriptor>,
+    shaders: &mut Assets<Shader>,
+    render_graph: &mut RenderGraph,
+) -> RenderPipelines {
+    let pipeline_handle = pipelines.add(PipelineDescriptor::default_config(ShaderStages {
+        vertex: shaders.add(Shader::from_glsl(
+            ShaderStage::Vertex,
+            include_str!("../assets/cloud_shader.vert"),
+        )),
+        fragment: Some(shaders.add(Shader::from_glsl(
+            ShaderStage::Fragment,
+            &glsl(include_str!("../assets/cloud_shader.frag")),
+        ))),
+    }));
+
+    render_graph.add_system_node(
+        "cloud_material",
+        AssetRenderResourcesNode::<CloudMaterial>::new(true),
+    );
+    render_graph
+        .add_node_edge("cloud_material", base::node::MAIN_PASS)
+        .unwrap();
+
+    RenderPipelines::from_pipelines(vec![RenderPipeline::specialized(
+        pipeline_handle,
+        PipelineSpecialization {
+            dynamic_bindings: vec![
+                // Transform
+                DynamicBinding {
+                    bind_group: 1,
+                    binding: 0,
+                },
+                // CloudMaterial_color
+                DynamicBinding {
+                    bind_group: 1,
+                    binding: 1,
+                },
+                // CloudMaterial_coverage
+                DynamicBinding {
+                    bind_group: 1,
+                    binding: 2,
+                },
+                // CloudMaterial_speed
+                DynamicBinding {
+                    bind_group: 1,
+                    binding: 3,
+                },
+                // CloudMaterial_time
+                DynamicBinding {
+                    bind_group: 1,
+                    binding: 4,
+                },
+                // CloudMaterial_sun_position
+                DynamicBinding {
+                    bind_group: 1,
+                    binding: 5,
+                },
+            ],
+            ..Default::default()
+        },
+    )])
+}
+
+// keeps the shader in step with the clock and with any changes to the planet config
+pub fn update_clouds(
+    time: Res<Time>,
+    config: Res<PlanetConfig>,
+    mut materials: ResMut<Assets<CloudMaterial>>,
+) {
+    let mut handles = Vec::new();
+    for mat in materials.iter() {
+        handles.push(mat.0);
+    }
+    for handle in handles {
+        if let Some(mat) = materials.get_mut(&handle) {
+            mat.time = time.seconds_since_startup as f32;
+            mat.coverage = config.clouds.coverage;
+            mat.speed = config.clouds.speed;
+            mat.color = config.clouds.color;
+        }
+    }
+}
@@ -25,29 +25,56 @@ unsafe impl Byteable for Vertex {}
 
 use super::Vertex;
 use crate::{
+    color::Color,
+    draw::{Draw, RenderCommand},
     pipeline::{
-        AsVertexBufferDescriptor, IndexFormat, PrimitiveTopology, RenderPipelines,
+        AsVertexBufferDescriptor, IndexFormat, InputStepMode, PipelineCompiler,
+        PipelineDescriptor, PipelineLayout, PrimitiveTopology, RenderPipelines,
         VertexBufferDescriptor, VertexBufferDescriptors, VertexFormat,
     },
-    renderer::{BufferInfo, BufferUsage, RenderResourceContext, RenderResourceId},
+    render_graph::{Node, ResourceSlots},
+    renderer::{
+        BufferId, BufferInfo, BufferUsage, RenderContext, RenderResourceContext,
+        RenderResourceId,
+    },
 };
 use bevy_app::prelude::{EventReader, Events};
 use bevy_asset::{AssetEvent, Assets, Handle};
 use bevy_core::AsBytes;
-use bevy_ecs::{Local, Query, Res, ResMut};
+use bevy_ecs::{Local, Query, Res, ResMut, Resources, World};
 use bevy_math::*;
-use bevy_utils::HashSet;
-use std::borrow::Cow;
+use bevy_utils::{HashMap, HashSet};
+use std::{
+    borrow::Cow,
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    ops::Range,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+};
 use thiserror::Error;
 
 pub const VERTEX_BUFFER_ASSET_INDEX: usize = 0;
 pub const INDEX_BUFFER_ASSET_INDEX: usize = 1;
+/// Vertex buffers for layouts other than `Vertex` get asset indices from here on.
+pub const CUSTOM_VERTEX_BUFFER_ASSET_INDEX: usize = 2;
 #[derive(Clone, Debug)]
 pub enum VertexAttributeValues {
     Float(Vec<f32>),
     Float2(Vec<[f32; 2]>),
     Float3(Vec<[f32; 3]>),
     Float4(Vec<[f32; 4]>),
+    Uint(Vec<u32>),
+    Int(Vec<i32>),
+    /// 0..255 read as 0.0..1.0 in the shader, plenty for colours
+    Uchar4Norm(Vec<[u8; 4]>),
+    /// -32767..32767 read as -1.0..1.0 in the shader
+    Short2Norm(Vec<[i16; 2]>),
+    /// raw half float bits, see `f32_to_f16`
+    Half2(Vec<[u16; 2]>),
+    Half4(Vec<[u16; 4]>),
 }
 
 impl VertexAttributeValues {
@@ -57,6 +84,12 @@ impl VertexAttributeValues {
             VertexAttributeValues::Float2(ref values) => values.len(),
             VertexAttributeValues::Float3(ref values) => values.len(),
             VertexAttributeValues::Float4(ref values) => values.len(),
+            VertexAttributeValues::Uint(ref values) => values.len(),
+            VertexAttributeValues::Int(ref values) => values.len(),
+            VertexAttributeValues::Uchar4Norm(ref values) => values.len(),
+            VertexAttributeValues::Short2Norm(ref values) => values.len(),
+            VertexAttributeValues::Half2(ref values) => values.len(),
+            VertexAttributeValues::Half4(ref values) => values.len(),
         }
     }
 
@@ -64,17 +97,255 @@ impl VertexAttributeValues {
         self.len() == 0
     }
 
-    // TODO: add vertex format as parameter here and perform type conversions
+    /// The values as they're stored, see `get_bytes_as` to upload them in another format.
+    /// Appends `other` if it holds the same type, `false` if it doesn't.
+    pub fn extend(&mut self, other: &VertexAttributeValues) -> bool {
+        match (self, other) {
+            (VertexAttributeValues::Float(values), VertexAttributeValues::Float(other)) => {
+                values.extend_from_slice(other)
+            }
+            (VertexAttributeValues::Float2(values), VertexAttributeValues::Float2(other)) => {
+                values.extend_from_slice(other)
+            }
+            (VertexAttributeValues::Float3(values), VertexAttributeValues::Float3(other)) => {
+                values.extend_from_slice(other)
+            }
+            (VertexAttributeValues::Float4(values), VertexAttributeValues::Float4(other)) => {
+                values.extend_from_slice(other)
+            }
+            (VertexAttributeValues::Uint(values), VertexAttributeValues::Uint(other)) => {
+                values.extend_from_slice(other)
+            }
+            (VertexAttributeValues::Int(values), VertexAttributeValues::Int(other)) => {
+                values.extend_from_slice(other)
+            }
+            (
+                VertexAttributeValues::Uchar4Norm(values),
+                VertexAttributeValues::Uchar4Norm(other),
+            ) => values.extend_from_slice(other),
+            (
+                VertexAttributeValues::Short2Norm(values),
+                VertexAttributeValues::Short2Norm(other),
+            ) => values.extend_from_slice(other),
+            (VertexAttributeValues::Half2(values), VertexAttributeValues::Half2(other)) => {
+                values.extend_from_slice(other)
+            }
+            (VertexAttributeValues::Half4(values), VertexAttributeValues::Half4(other)) => {
+                values.extend_from_slice(other)
+            }
+            _ => return false,
+        }
+        true
+    }
+
     pub fn get_bytes(&self) -> &[u8] {
         match self {
             VertexAttributeValues::Float(values) => values.as_slice().as_bytes(),
             VertexAttributeValues::Float2(values) => values.as_slice().as_bytes(),
             VertexAttributeValues::Float3(values) => values.as_slice().as_bytes(),
             VertexAttributeValues::Float4(values) => values.as_slice().as_bytes(),
+            VertexAttributeValues::Uint(values) => values.as_slice().as_bytes(),
+            VertexAttributeValues::Int(values) => values.as_slice().as_bytes(),
+            VertexAttributeValues::Uchar4Norm(values) => values.as_slice().as_bytes(),
+            VertexAttributeValues::Short2Norm(values) => values.as_slice().as_bytes(),
+            VertexAttributeValues::Half2(values) => values.as_slice().as_bytes(),
+            VertexAttributeValues::Half4(values) => values.as_slice().as_bytes(),
+        }
+    }
+
+    /// The values converted to `format`, borrowed if they're already in it. `None` if there's
+    /// no conversion between the two.
+    pub fn get_bytes_as(&self, format: VertexFormat) -> Option<Cow<[u8]>> {
+        if VertexFormat::from(self) == format {
+            return Some(Cow::Borrowed(self.get_bytes()));
+        }
+        self.convert(format)
+            .map(|values| Cow::Owned(values.get_bytes().to_vec()))
+    }
+
+    /// Converts the values to `format`, `None` if there's no conversion between the two.
+    /// Packing floats into the normalized and half formats loses precision, normalized
+    /// formats also clamp to their range.
+    pub fn convert(&self, format: VertexFormat) -> Option<VertexAttributeValues> {
+        use VertexAttributeValues::*;
+        Some(match (self, format) {
+            (values, format) if VertexFormat::from(values) == format => values.clone(),
+            (Float4(values), VertexFormat::Uchar4Norm) => Uchar4Norm(
+                values
+                    .iter()
+                    .map(|v| [unorm8(v[0]), unorm8(v[1]), unorm8(v[2]), unorm8(v[3])])
+                    .collect(),
+            ),
+            (Uchar4Norm(values), VertexFormat::Float4) => Float4(
+                values
+                    .iter()
+                    .map(|v| {
+                        [
+                            v[0] as f32 / 255.0,
+                            v[1] as f32 / 255.0,
+                            v[2] as f32 / 255.0,
+                            v[3] as f32 / 255.0,
+                        ]
+                    })
+                    .collect(),
+            ),
+            (Float2(values), VertexFormat::Short2Norm) => Short2Norm(
+                values
+                    .iter()
+                    .map(|v| [snorm16(v[0]), snorm16(v[1])])
+                    .collect(),
+            ),
+            (Short2Norm(values), VertexFormat::Float2) => Float2(
+                values
+                    .iter()
+                    .map(|v| {
+                        [
+                            (v[0] as f32 / 32767.0).max(-1.0),
+                            (v[1] as f32 / 32767.0).max(-1.0),
+                        ]
+                    })
+                    .collect(),
+            ),
+            (Float2(values), VertexFormat::Half2) => Half2(
+                values
+                    .iter()
+                    .map(|v| [f32_to_f16(v[0]), f32_to_f16(v[1])])
+                    .collect(),
+            ),
+            (Half2(values), VertexFormat::Float2) => Float2(
+                values
+                    .iter()
+                    .map(|v| [f16_to_f32(v[0]), f16_to_f32(v[1])])
+                    .collect(),
+            ),
+            (Float4(values), VertexFormat::Half4) => Half4(
+                values
+                    .iter()
+                    .map(|v| {
+                        [
+                            f32_to_f16(v[0]),
+                            f32_to_f16(v[1]),
+                            f32_to_f16(v[2]),
+                            f32_to_f16(v[3]),
+                        ]
+                    })
+                    .collect(),
+            ),
+            (Half4(values), VertexFormat::Float4) => Float4(
+                values
+                    .iter()
+                    .map(|v| {
+                        [
+                            f16_to_f32(v[0]),
+                            f16_to_f32(v[1]),
+                            f16_to_f32(v[2]),
+                            f16_to_f32(v[3]),
+                        ]
+                    })
+                    .collect(),
+            ),
+            (Uint(values), VertexFormat::Float) => {
+                Float(values.iter().map(|v| *v as f32).collect())
+            }
+            (Int(values), VertexFormat::Float) => Float(values.iter().map(|v| *v as f32).collect()),
+            _ => return None,
+        })
+    }
+}
+
+fn unorm8(value: f32) -> u8 {
+    (value.max(0.0).min(1.0) * 255.0).round() as u8
+}
+
+fn snorm16(value: f32) -> i16 {
+    (value.max(-1.0).min(1.0) * 32767.0).round() as i16
+}
+
+/// Bits of the nearest half float. Too large values become infinity, too small ones zero.
+pub fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x007f_ffff;
+    if exponent == 0xff {
+        // infinity stays infinity, NaN stays NaN
+        return sign | 0x7c00 | if mantissa != 0 { 0x0200 } else { 0 };
+    }
+
+    let exponent = exponent - 127 + 15;
+    if exponent >= 0x1f {
+        return sign | 0x7c00;
+    }
+    if exponent <= 0 {
+        if exponent < -10 {
+            return sign;
         }
+        // subnormal, the implicit leading one has to be stored
+        let mantissa = mantissa | 0x0080_0000;
+        let shift = (14 - exponent) as u32;
+        let round = (mantissa >> (shift - 1)) & 1;
+        return sign | ((mantissa >> shift) + round) as u16;
     }
+    // rounding can carry into the exponent, which still gives the right answer
+    let round = ((mantissa >> 12) & 1) as u16;
+    (sign | ((exponent as u16) << 10) | (mantissa >> 13) as u16) + round
 }
 
+pub fn f16_to_f32(half: u16) -> f32 {
+    let sign = ((half & 0x8000) as u32) << 16;
+    let exponent = ((half >> 10) & 0x1f) as u32;
+    let mantissa = (half & 0x03ff) as u32;
+    let bits = match exponent {
+        0 if mantissa == 0 => sign,
+        0 => {
+            // subnormal, shift until the leading one becomes implicit
+            let shift = mantissa.leading_zeros() - 21;
+            let mantissa = (mantissa << shift) & 0x03ff;
+            sign | ((113 - shift) << 23) | (mantissa << 13)
+        }
+        0x1f => sign | 0x7f80_0000 | (mantissa << 13),
+        _ => sign | ((exponent + 127 - 15) << 23) | (mantissa << 13),
+    };
+    f32::from_bits(bits)
+}
+
+/// Element types a `VertexAttributeValues` can be viewed as, see `Mesh::attribute`.
+pub trait VertexAttributeType: Sized {
+    fn values(values: &VertexAttributeValues) -> Option<&[Self]>;
+    fn values_mut(values: &mut VertexAttributeValues) -> Option<&mut [Self]>;
+}
+
+macro_rules! impl_vertex_attribute_type {
+    ($ty:ty, $variant:ident) => {
+        impl VertexAttributeType for $ty {
+            fn values(values: &VertexAttributeValues) -> Option<&[Self]> {
+                match values {
+                    VertexAttributeValues::$variant(values) => Some(values),
+                    _ => None,
+                }
+            }
+
+            fn values_mut(values: &mut VertexAttributeValues) -> Option<&mut [Self]> {
+                match values {
+                    VertexAttributeValues::$variant(values) => Some(values),
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+impl_vertex_attribute_type!(f32, Float);
+impl_vertex_attribute_type!([f32; 2], Float2);
+impl_vertex_attribute_type!([f32; 3], Float3);
+impl_vertex_attribute_type!([f32; 4], Float4);
+impl_vertex_attribute_type!(u32, Uint);
+impl_vertex_attribute_type!(i32, Int);
+impl_vertex_attribute_type!([u8; 4], Uchar4Norm);
+impl_vertex_attribute_type!([i16; 2], Short2Norm);
+impl_vertex_attribute_type!([u16; 2], Half2);
+impl_vertex_attribute_type!([u16; 4], Half4);
+
 impl From<&VertexAttributeValues> for VertexFormat {
     fn from(values: &VertexAttributeValues) -> Self {
         match values {
@@ -82,11 +353,17 @@ impl From<&VertexAttributeValues> for VertexFormat {
             VertexAttributeValues::Float2(_) => VertexFormat::Float2,
             VertexAttributeValues::Float3(_) => VertexFormat::Float3,
             VertexAttributeValues::Float4(_) => VertexFormat::Float4,
+            VertexAttributeValues::Uint(_) => VertexFormat::Uint,
+            VertexAttributeValues::Int(_) => VertexFormat::Int,
+            VertexAttributeValues::Uchar4Norm(_) => VertexFormat::Uchar4Norm,
+            VertexAttributeValues::Short2Norm(_) => VertexFormat::Short2Norm,
+            VertexAttributeValues::Half2(_) => VertexFormat::Half2,
+            VertexAttributeValues::Half4(_) => VertexFormat::Half4,
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct VertexAttribute {
     pub name: Cow<'static, str>,
     pub values: VertexAttributeValues,
@@ -97,6 +374,7 @@ impl VertexAttribute {
     pub const POSITION: &'static str = "Vertex_Position";
     pub const UV: &'static str = "Vertex_Uv";
     pub const COLOR: &'static str = "Vertex_Color";
+    pub const TANGENT: &'static str = "Vertex_Tangent";
 
     pub fn position(positions: Vec<[f32; 3]>) -> Self {
         VertexAttribute {
@@ -125,6 +403,15 @@ impl VertexAttribute {
             values: VertexAttributeValues::Float4(colors),
         }
     }
+
+    /// Tangents point along increasing u. `w` is the handedness, the bitangent is
+    /// `cross(normal, tangent.xyz) * tangent.w` like in MikkTSpace.
+    pub fn tangent(tangents: Vec<[f32; 4]>) -> Self {
+        VertexAttribute {
+            name: Self::TANGENT.into(),
+            values: VertexAttributeValues::Float4(tangents),
+        }
+    }
 }
 
 #[derive(Error, Debug)]
@@ -139,11 +426,239 @@ pub enum MeshToVertexBufferError {
     },
 }
 
+#[derive(Error, Debug)]
+pub enum MeshValidationError {
+    #[error("Mesh VertexAttribute {name} has {len} values, the other attributes have {expected}.")]
+    MismatchedAttributeLength {
+        name: Cow<'static, str>,
+        len: usize,
+        expected: usize,
+    },
+    #[error("TriangleList meshes need a multiple of three indices (or vertices), not {0}.")]
+    IncompleteTriangle(usize),
+    #[error("Mesh index {index} is out of range for {vertex_count} vertices.")]
+    IndexOutOfRange { index: u32, vertex_count: usize },
+    #[error("Mesh index {index} doesn't fit in IndexFormat {index_format:?}.")]
+    IndexFormatOverflow {
+        index: u32,
+        index_format: IndexFormat,
+    },
+    #[error("Mesh vertex {vertex} has a NaN or infinite position {position:?}.")]
+    NonFinitePosition { vertex: usize, position: [f32; 3] },
+}
+
+#[derive(Error, Debug)]
+pub enum GenerateTangentsError {
+    #[error("Tangents can only be generated for TriangleList meshes.")]
+    UnsupportedTopology(PrimitiveTopology),
+    #[error("Generating tangents requires the {0} VertexAttribute.")]
+    MissingVertexAttribute(&'static str),
+}
+
+#[derive(Error, Debug)]
+pub enum FlatShadingError {
+    #[error("Flat shading needs a mesh without indices, see Mesh::duplicate_vertices.")]
+    Indexed,
+    #[error("Flat shading only works on TriangleList meshes.")]
+    UnsupportedTopology(PrimitiveTopology),
+}
+
+#[derive(Error, Debug)]
+pub enum MergeMeshesError {
+    #[error("Cannot merge a {0:?} mesh into a {1:?} mesh.")]
+    MismatchedTopology(PrimitiveTopology, PrimitiveTopology),
+    #[error("Every merged mesh needs the same attributes, {0} is missing or has another type.")]
+    MismatchedAttribute(Cow<'static, str>),
+}
+
+/// An axis aligned box, around a mesh in its own space unless it's been `transformed`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    pub fn from_points(points: impl IntoIterator<Item = Vec3>) -> Option<Self> {
+        let mut points = points.into_iter();
+        let first = points.next()?;
+        Some(points.fold(
+            Aabb {
+                min: first,
+                max: first,
+            },
+            |aabb, point| Aabb {
+                min: aabb.min.min(point),
+                max: aabb.max.max(point),
+            },
+        ))
+    }
+
+    pub fn center(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn half_extents(&self) -> Vec3 {
+        (self.max - self.min) * 0.5
+    }
+
+    /// The box around all eight corners after `transform`, a little looser than the
+    /// original once there's rotation involved.
+    pub fn transformed(&self, transform: &Mat4) -> Self {
+        let corners = (0..8).map(|i| {
+            let pick = |bit: i32, min: f32, max: f32| if i & bit == 0 { min } else { max };
+            let corner = Vec3::new(
+                pick(1, self.min.x(), self.max.x()),
+                pick(2, self.min.y(), self.max.y()),
+                pick(4, self.min.z(), self.max.z()),
+            );
+            (*transform * corner.extend(1.0)).truncate()
+        });
+        Aabb::from_points(corners).unwrap()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BoundingSphere {
+    pub center: Vec3,
+    pub radius: f32,
+}
+
+impl BoundingSphere {
+    /// `transform` may scale, the radius grows with its largest axis.
+    pub fn transformed(&self, transform: &Mat4) -> Self {
+        let scale = transform
+            .x_axis()
+            .truncate()
+            .length()
+            .max(transform.y_axis().truncate().length())
+            .max(transform.z_axis().truncate().length());
+        BoundingSphere {
+            center: (*transform * self.center.extend(1.0)).truncate(),
+            radius: self.radius * scale,
+        }
+    }
+}
+
+/// Both kinds of bounds of a mesh, see `Mesh::bounds`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Bounds {
+    pub aabb: Aabb,
+    pub sphere: BoundingSphere,
+}
+
+impl Bounds {
+    /// Bounds around all of `bounds`, `None` if there are none. The sphere is centered on the
+    /// box, so it's looser than one computed from the positions.
+    pub fn enclosing(bounds: &[Bounds]) -> Option<Self> {
+        let aabb = Aabb::from_points(bounds.iter().flat_map(|bounds| {
+            std::iter::once(bounds.aabb.min).chain(std::iter::once(bounds.aabb.max))
+        }))?;
+        let center = aabb.center();
+        let radius = bounds
+            .iter()
+            .map(|bounds| (bounds.sphere.center - center).length() + bounds.sphere.radius)
+            .fold(0.0, f32::max);
+        Some(Bounds {
+            aabb,
+            sphere: BoundingSphere { center, radius },
+        })
+    }
+}
+
+/// `Mesh::bounds` as of the last change to the positions. Behind a lock so it can be filled
+/// in through `Res<Assets<Mesh>>`, like `DirtyRange`.
+#[derive(Debug, Default)]
+pub struct CachedBounds {
+    // the outer `None` means not computed yet
+    bounds: Mutex<Option<Option<Bounds>>>,
+}
+
+// a copy has the same positions, so the same bounds
+impl Clone for CachedBounds {
+    fn clone(&self) -> Self {
+        CachedBounds {
+            bounds: Mutex::new(*self.bounds.lock().unwrap()),
+        }
+    }
+}
+
+impl CachedBounds {
+    /// Makes the next `Mesh::bounds` compute them again. Only needed after writing positions
+    /// through `Mesh::attributes` directly, the other ways of changing them do it already.
+    pub fn invalidate(&self) {
+        *self.bounds.lock().unwrap() = None;
+    }
+
+    fn set(&self, bounds: Option<Bounds>) {
+        *self.bounds.lock().unwrap() = Some(bounds);
+    }
+}
+
+/// The vertices changed since the mesh was last uploaded. Lives behind atomics so
+/// `mesh_resource_provider_system` can take it through `Res<Assets<Mesh>>`.
 #[derive(Debug)]
+pub struct DirtyRange {
+    start: AtomicUsize,
+    end: AtomicUsize,
+}
+
+impl Default for DirtyRange {
+    fn default() -> Self {
+        DirtyRange {
+            start: AtomicUsize::new(usize::MAX),
+            end: AtomicUsize::new(0),
+        }
+    }
+}
+
+// a copy is a separate asset, it starts out with the same pending changes
+impl Clone for DirtyRange {
+    fn clone(&self) -> Self {
+        DirtyRange {
+            start: AtomicUsize::new(self.start.load(Ordering::Relaxed)),
+            end: AtomicUsize::new(self.end.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+impl DirtyRange {
+    /// Grows the range to cover `vertices` as well.
+    pub fn mark(&self, vertices: Range<usize>) {
+        if vertices.start < vertices.end {
+            self.start.fetch_min(vertices.start, Ordering::Relaxed);
+            self.end.fetch_max(vertices.end, Ordering::Relaxed);
+        }
+    }
+
+    /// Forgets the marked range so the next upload rewrites the vertex buffers whole.
+    pub fn invalidate(&self) {
+        self.take();
+    }
+
+    /// The range marked so far, leaving it clean.
+    pub fn take(&self) -> Option<Range<usize>> {
+        let start = self.start.swap(usize::MAX, Ordering::Relaxed);
+        let end = self.end.swap(0, Ordering::Relaxed);
+        if start < end {
+            Some(start..end)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
 pub struct Mesh {
     pub primitive_topology: PrimitiveTopology,
     pub attributes: Vec<VertexAttribute>,
     pub indices: Option<Vec<u32>>,
+    /// Set this when only some vertices changed, the next upload then only writes those
+    /// vertices. Left clean, a modified mesh is uploaded whole. Changed indices are found on
+    /// upload either way.
+    pub dirty_vertices: DirtyRange,
+    /// Cache for `Mesh::bounds`.
+    pub bounds: CachedBounds,
 }
 
 impl Mesh {
@@ -152,9 +667,92 @@ impl Mesh {
             primitive_topology,
             attributes: Vec::new(),
             indices: None,
+            dirty_vertices: DirtyRange::default(),
+            bounds: CachedBounds::default(),
+        }
+    }
+
+    /// The values of the attribute called `name`, `None` if it's missing or doesn't hold `T`.
+    pub fn attribute<T: VertexAttributeType>(&self, name: &str) -> Option<&[T]> {
+        self.attributes
+            .iter()
+            .find(|attribute| attribute.name == name)
+            .and_then(|attribute| T::values(&attribute.values))
+    }
+
+    /// The values of the attribute called `name` to write to. All of them count as changed,
+    /// see `attribute_range_mut` for writing only some.
+    pub fn attribute_mut<T: VertexAttributeType>(&mut self, name: &str) -> Option<&mut [T]> {
+        let index = self
+            .attributes
+            .iter()
+            .position(|attribute| attribute.name == name)?;
+        // asking for the wrong type hands nothing out, so nothing counts as changed either
+        T::values(&self.attributes[index].values)?;
+        self.attribute_changed(name);
+        T::values_mut(&mut self.attributes[index].values)
+    }
+
+    /// Like `attribute_mut` for just the `vertices` range, which gets marked dirty so the
+    /// next upload only writes those. That covers every vertex layout the mesh is packed into,
+    /// the indices are compared on upload and rewritten if they changed.
+    pub fn attribute_range_mut<T: VertexAttributeType>(
+        &mut self,
+        name: &str,
+        vertices: Range<usize>,
+    ) -> Option<&mut [T]> {
+        let index = self
+            .attributes
+            .iter()
+            .position(|attribute| attribute.name == name)?;
+        T::values(&self.attributes[index].values)?.get(vertices.clone())?;
+        self.positions_changed(name);
+        self.dirty_vertices.mark(vertices.clone());
+        T::values_mut(&mut self.attributes[index].values)?.get_mut(vertices)
+    }
+
+    /// Adds `attribute`, replacing the one with the same name. A replaced attribute keeps its
+    /// place in the list.
+    pub fn insert_attribute(&mut self, attribute: VertexAttribute) {
+        let name = attribute.name.clone();
+        match self
+            .attributes
+            .iter_mut()
+            .find(|existing| existing.name == attribute.name)
+        {
+            Some(existing) => *existing = attribute,
+            None => self.attributes.push(attribute),
+        }
+        self.attribute_changed(&name);
+    }
+
+    pub fn remove_attribute(&mut self, name: &str) -> Option<VertexAttribute> {
+        let index = self
+            .attributes
+            .iter()
+            .position(|attribute| attribute.name == name)?;
+        self.attribute_changed(name);
+        Some(self.attributes.remove(index))
+    }
+
+    fn positions_changed(&self, name: &str) {
+        if name == VertexAttribute::POSITION {
+            self.bounds.invalidate();
         }
     }
 
+    /// Marks every vertex dirty, so a pending partial update doesn't leave this change out.
+    fn attribute_changed(&self, name: &str) {
+        self.positions_changed(name);
+        self.dirty_vertices.mark(0..self.vertex_count());
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        self.attributes.first().map(|a| a.values.len()).unwrap_or(0)
+    }
+
+    /// Packs the attributes `vertex_buffer_descriptor` asks for. A missing attribute is an
+    /// error, or with `fill_missing_attributes` white for colours and zeros otherwise.
     pub fn get_vertex_buffer_bytes(
         &self,
         vertex_buffer_descriptor: &VertexBufferDescriptor,
@@ -164,34 +762,69 @@ impl Mesh {
         let mut bytes = vec![0; vertex_buffer_descriptor.stride as usize * length];
 
         for vertex_attribute in vertex_buffer_descriptor.attributes.iter() {
-            match self
+            let mesh_attribute_name =
+                mesh_attribute_name(&vertex_buffer_descriptor.name, &vertex_attribute.name);
+            let attribute_bytes = match self
                 .attributes
                 .iter()
-                .find(|a| vertex_attribute.name == a.name)
+                .find(|a| mesh_attribute_name == a.name)
             {
-                Some(mesh_attribute) => {
-                    let attribute_bytes = mesh_attribute.values.get_bytes();
-                    let attribute_size = vertex_attribute.format.get_size() as usize;
-                    for (i, vertex_slice) in attribute_bytes.chunks(attribute_size).enumerate() {
-                        let vertex_offset = vertex_buffer_descriptor.stride as usize * i;
-                        let attribute_offset = vertex_offset + vertex_attribute.offset as usize;
-                        bytes[attribute_offset..attribute_offset + attribute_size]
-                            .copy_from_slice(vertex_slice);
-                    }
-                }
-                None => {
-                    if !fill_missing_attributes {
-                        return Err(MeshToVertexBufferError::MissingVertexAttribute {
+                Some(mesh_attribute) => mesh_attribute
+                    .values
+                    .get_bytes_as(vertex_attribute.format)
+                    .ok_or_else(
+                        || MeshToVertexBufferError::IncompatibleVertexAttributeFormat {
                             attribute_name: vertex_attribute.name.clone(),
-                        });
+                            descriptor_format: vertex_attribute.format,
+                            mesh_format: (&mesh_attribute.values).into(),
+                        },
+                    )?,
+                None if !fill_missing_attributes => {
+                    return Err(MeshToVertexBufferError::MissingVertexAttribute {
+                        attribute_name: vertex_attribute.name.clone(),
+                    });
+                }
+                // meshes without colours are drawn white, everything else missing is zeros
+                None if mesh_attribute_name == VertexAttribute::COLOR => {
+                    match VertexAttributeValues::Float4(vec![[1.0; 4]; length])
+                        .get_bytes_as(vertex_attribute.format)
+                    {
+                        Some(bytes) => Cow::Owned(bytes.into_owned()),
+                        None => continue,
                     }
                 }
+                None => continue,
+            };
+            let attribute_size = vertex_attribute.format.get_size() as usize;
+            for (i, vertex_slice) in attribute_bytes.chunks(attribute_size).enumerate() {
+                let vertex_offset = vertex_buffer_descriptor.stride as usize * i;
+                let attribute_offset = vertex_offset + vertex_attribute.offset as usize;
+                bytes[attribute_offset..attribute_offset + attribute_size]
+                    .copy_from_slice(vertex_slice);
             }
         }
 
         Ok(bytes)
     }
 
+    /// The indices, or `0..vertex_count` for a mesh without any.
+    pub fn triangle_indices(&self) -> Cow<[u32]> {
+        match &self.indices {
+            Some(indices) => Cow::Borrowed(indices),
+            None => Cow::Owned((0..self.vertex_count() as u32).collect()),
+        }
+    }
+
+    /// The index format the mesh is uploaded and drawn with, 32 bit once 16 bit indices can't
+    /// reach every vertex.
+    pub fn index_format(&self) -> IndexFormat {
+        if self.vertex_count() > std::u16::MAX as usize {
+            IndexFormat::Uint32
+        } else {
+            IndexFormat::Uint16
+        }
+    }
+
     pub fn get_index_buffer_bytes(&self, index_format: IndexFormat) -> Option<Vec<u8>> {
         self.indices.as_ref().map(|indices| match index_format {
             IndexFormat::Uint16 => indices
@@ -204,110 +837,551 @@ impl Mesh {
             IndexFormat::Uint32 => indices.as_slice().as_bytes().to_vec(),
         })
     }
-}
-
-/// Generation for some primitive shape meshes.
-pub mod shape {
-    use super::{Mesh, VertexAttribute};
-    use crate::pipeline::PrimitiveTopology;
-    use bevy_math::*;
-    use hexasphere::Hexasphere;
-
-    /// A cube.
-    pub struct Cube {
-        /// Half the side length of the cube.
-        pub size: f32,
-    }
 
-    impl Default for Cube {
-        fn default() -> Self {
-            Cube { size: 1.0 }
+    /// Checks the mesh can be uploaded with `index_format` and drawn without reading garbage.
+    pub fn validate(&self, index_format: IndexFormat) -> Result<(), MeshValidationError> {
+        let vertex_count = self.vertex_count();
+        for attribute in self.attributes.iter() {
+            if attribute.values.len() != vertex_count {
+                return Err(MeshValidationError::MismatchedAttributeLength {
+                    name: attribute.name.clone(),
+                    len: attribute.values.len(),
+                    expected: vertex_count,
+                });
+            }
         }
-    }
-
-    impl From<Cube> for Mesh {
-        fn from(cube: Cube) -> Self {
-            let size = cube.size;
-            let vertices = &[
-                // top (0., 0., size)
-                ([-size, -size, size], [0., 0., size], [0., 0.], [1.0, 1.0, 1.0, 1.0]),
-                ([size, -size, size], [0., 0., size], [size, 0.], [1.0, 1.0, 1.0, 1.0]),
-                ([size, size, size], [0., 0., size], [size, size], [1.0, 1.0, 1.0, 1.0]),
-                ([-size, size, size], [0., 0., size], [0., size], [1.0, 1.0, 1.0, 1.0]),
-                // bottom (0., 0., -size)
-                ([-size, size, -size], [0., 0., -size], [size, 0.], [1.0, 1.0, 1.0, 1.0]),
-                ([size, size, -size], [0., 0., -size], [0., 0.], [1.0, 1.0, 1.0, 1.0]),
-                ([size, -size, -size], [0., 0., -size], [0., size], [1.0, 1.0, 1.0, 1.0]),
-                ([-size, -size, -size], [0., 0., -size], [size, size], [1.0, 1.0, 1.0, 1.0]),
-                // right (size, 0., 0.)
-                ([size, -size, -size], [size, 0., 0.], [0., 0.], [1.0, 1.0, 1.0, 1.0]),
-                ([size, size, -size], [size, 0., 0.], [size, 0.], [1.0, 1.0, 1.0, 1.0]),
-                ([size, size, size], [size, 0., 0.], [size, size], [1.0, 1.0, 1.0, 1.0]),
-                ([size, -size, size], [size, 0., 0.], [0., size], [1.0, 1.0, 1.0, 1.0]),
-                // left (-size, 0., 0.)
-                ([-size, -size, size], [-size, 0., 0.], [size, 0.], [1.0, 1.0, 1.0, 1.0]),
-                ([-size, size, size], [-size, 0., 0.], [0., 0.], [1.0, 1.0, 1.0, 1.0]),
-                ([-size, size, -size], [-size, 0., 0.], [0., size], [1.0, 1.0, 1.0, 1.0]),
-                ([-size, -size, -size], [-size, 0., 0.], [size, size], [1.0, 1.0, 1.0, 1.0]),
-                // front (0., size, 0.)
-                ([size, size, -size], [0., size, 0.], [size, 0.], [1.0, 1.0, 1.0, 1.0]),
-                ([-size, size, -size], [0., size, 0.], [0., 0.], [1.0, 1.0, 1.0, 1.0]),
-                ([-size, size, size], [0., size, 0.], [0., size], [1.0, 1.0, 1.0, 1.0]),
-                ([size, size, size], [0., size, 0.], [size, size], [1.0, 1.0, 1.0, 1.0]),
-                // back (0., -size, 0.)
-                ([size, -size, size], [0., -size, 0.], [0., 0.], [1.0, 1.0, 1.0, 1.0]),
-                ([-size, -size, size], [0., -size, 0.], [size, 0.], [1.0, 1.0, 1.0, 1.0]),
-                ([-size, -size, -size], [0., -size, 0.], [size, size], [1.0, 1.0, 1.0, 1.0]),
-                ([size, -size, -size], [0., -size, 0.], [0., size], [1.0, 1.0, 1.0, 1.0]),
-            ];
 
-            let mut positions = Vec::new();
-            let mut normals = Vec::new();
-            let mut uvs = Vec::new();
-            let mut colors = Vec::new();
-            for (position, normal, uv, color) in vertices.iter() {
-                positions.push(*position);
-                normals.push(*normal);
-                uvs.push(*uv);
-                colors.push(*color);
+        let triangle_list = self.primitive_topology == PrimitiveTopology::TriangleList;
+        if self.indices.is_none() && triangle_list && vertex_count % 3 != 0 {
+            return Err(MeshValidationError::IncompleteTriangle(vertex_count));
+        }
+        if let Some(indices) = &self.indices {
+            if triangle_list && indices.len() % 3 != 0 {
+                return Err(MeshValidationError::IncompleteTriangle(indices.len()));
             }
-
-            let indices = vec![
-                0, 1, 2, 2, 3, 0, // top
-                4, 5, 6, 6, 7, 4, // bottom
-                8, 9, 10, 10, 11, 8, // right
-                12, 13, 14, 14, 15, 12, // left
-                16, 17, 18, 18, 19, 16, // front
-                20, 21, 22, 22, 23, 20, // back
-            ];
-
-            Mesh {
-                primitive_topology: PrimitiveTopology::TriangleList,
-                attributes: vec![
-                    VertexAttribute::position(positions),
-                    VertexAttribute::normal(normals),
-                    VertexAttribute::uv(uvs),
-                    VertexAttribute::color(colors),
-                ],
-                indices: Some(indices),
+            let max_index = match index_format {
+                IndexFormat::Uint16 => std::u16::MAX as u32,
+                IndexFormat::Uint32 => std::u32::MAX,
+            };
+            for &index in indices.iter() {
+                if index > max_index {
+                    return Err(MeshValidationError::IndexFormatOverflow {
+                        index,
+                        index_format,
+                    });
+                }
+                if index as usize >= vertex_count {
+                    return Err(MeshValidationError::IndexOutOfRange {
+                        index,
+                        vertex_count,
+                    });
+                }
             }
         }
-    }
 
-    /// A rectangle on the XY plane.
-    pub struct Quad {
-        /// Full width and height of the rectangle.
-        pub size: Vec2,
-        /// Flips the texture coords of the resulting vertices.
-        pub flip: bool,
+        if let Some(positions) = self.attribute::<[f32; 3]>(VertexAttribute::POSITION) {
+            for (vertex, position) in positions.iter().enumerate() {
+                if !position.iter().all(|p| p.is_finite()) {
+                    return Err(MeshValidationError::NonFinitePosition {
+                        vertex,
+                        position: *position,
+                    });
+                }
+            }
+        }
+        Ok(())
     }
 
-    impl Quad {
-        pub fn new(size: Vec2) -> Self {
-            Self { size, flip: false }
+    /// Unindexes the mesh so every triangle gets its own vertices. Does nothing if the mesh has
+    /// no indices.
+    pub fn duplicate_vertices(&mut self) {
+        fn duplicate<T: Copy>(values: &[T], indices: &[u32]) -> Vec<T> {
+            indices.iter().map(|i| values[*i as usize]).collect()
         }
 
-        pub fn flipped(size: Vec2) -> Self {
+        let indices = match self.indices.take() {
+            Some(indices) => indices,
+            None => return,
+        };
+        for attribute in self.attributes.iter_mut() {
+            attribute.values = match &attribute.values {
+                VertexAttributeValues::Float(values) => {
+                    VertexAttributeValues::Float(duplicate(values, &indices))
+                }
+                VertexAttributeValues::Float2(values) => {
+                    VertexAttributeValues::Float2(duplicate(values, &indices))
+                }
+                VertexAttributeValues::Float3(values) => {
+                    VertexAttributeValues::Float3(duplicate(values, &indices))
+                }
+                VertexAttributeValues::Float4(values) => {
+                    VertexAttributeValues::Float4(duplicate(values, &indices))
+                }
+                VertexAttributeValues::Uint(values) => {
+                    VertexAttributeValues::Uint(duplicate(values, &indices))
+                }
+                VertexAttributeValues::Int(values) => {
+                    VertexAttributeValues::Int(duplicate(values, &indices))
+                }
+                VertexAttributeValues::Uchar4Norm(values) => {
+                    VertexAttributeValues::Uchar4Norm(duplicate(values, &indices))
+                }
+                VertexAttributeValues::Short2Norm(values) => {
+                    VertexAttributeValues::Short2Norm(duplicate(values, &indices))
+                }
+                VertexAttributeValues::Half2(values) => {
+                    VertexAttributeValues::Half2(duplicate(values, &indices))
+                }
+                VertexAttributeValues::Half4(values) => {
+                    VertexAttributeValues::Half4(duplicate(values, &indices))
+                }
+            };
+        }
+    }
+
+    /// Sets every normal to the normal of its triangle. The mesh can't be indexed, see
+    /// `duplicate_vertices`.
+    pub fn compute_flat_normals(&mut self) -> Result<(), FlatShadingError> {
+        self.check_flat_shading()?;
+        let normals = match self.attribute::<[f32; 3]>(VertexAttribute::POSITION) {
+            Some(positions) => positions
+                .chunks_exact(3)
+                .flat_map(|triangle| {
+                    let a = Vec3::from(triangle[0]);
+                    let normal = (Vec3::from(triangle[1]) - a)
+                        .cross(Vec3::from(triangle[2]) - a)
+                        .normalize();
+                    std::iter::repeat(normal.into()).take(3)
+                })
+                .collect::<Vec<[f32; 3]>>(),
+            _ => return Ok(()),
+        };
+        self.insert_attribute(VertexAttribute::normal(normals));
+        Ok(())
+    }
+
+    /// Gives every triangle the average colour of its corners. The mesh can't be indexed, see
+    /// `duplicate_vertices`.
+    pub fn compute_face_colors(&mut self) -> Result<(), FlatShadingError> {
+        self.check_flat_shading()?;
+        if let Some(colors) = self.attribute_mut::<[f32; 4]>(VertexAttribute::COLOR) {
+            for triangle in colors.chunks_exact_mut(3) {
+                let mut average = [0.0; 4];
+                for color in triangle.iter() {
+                    for (sum, channel) in average.iter_mut().zip(color.iter()) {
+                        *sum += channel / 3.0;
+                    }
+                }
+                for color in triangle.iter_mut() {
+                    *color = average;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn check_flat_shading(&self) -> Result<(), FlatShadingError> {
+        if self.indices.is_some() {
+            return Err(FlatShadingError::Indexed);
+        }
+        if self.primitive_topology != PrimitiveTopology::TriangleList {
+            return Err(FlatShadingError::UnsupportedTopology(
+                self.primitive_topology,
+            ));
+        }
+        Ok(())
+    }
+
+    /// Generates the `Vertex_Tangent` attribute from positions, normals, uvs and indices,
+    /// replacing any existing tangents.
+    pub fn generate_tangents(&mut self) -> Result<(), GenerateTangentsError> {
+        let tangents = self.compute_tangents()?;
+        self.insert_attribute(VertexAttribute::tangent(tangents));
+        Ok(())
+    }
+
+    fn compute_tangents(&self) -> Result<Vec<[f32; 4]>, GenerateTangentsError> {
+        if self.primitive_topology != PrimitiveTopology::TriangleList {
+            return Err(GenerateTangentsError::UnsupportedTopology(
+                self.primitive_topology,
+            ));
+        }
+        let missing = GenerateTangentsError::MissingVertexAttribute;
+        let positions = self
+            .attribute::<[f32; 3]>(VertexAttribute::POSITION)
+            .ok_or(missing(VertexAttribute::POSITION))?;
+        let normals = self
+            .attribute::<[f32; 3]>(VertexAttribute::NORMAL)
+            .ok_or(missing(VertexAttribute::NORMAL))?;
+        let uvs = self
+            .attribute::<[f32; 2]>(VertexAttribute::UV)
+            .ok_or(missing(VertexAttribute::UV))?;
+        let indices = self.triangle_indices();
+
+        // accumulate the uv derivatives of every triangle on its corners
+        let mut tangents = vec![Vec3::zero(); positions.len()];
+        let mut bitangents = vec![Vec3::zero(); positions.len()];
+        for triangle in indices.chunks_exact(3) {
+            let (i0, i1, i2) = (
+                triangle[0] as usize,
+                triangle[1] as usize,
+                triangle[2] as usize,
+            );
+            let p0 = Vec3::from(positions[i0]);
+            let edge1 = Vec3::from(positions[i1]) - p0;
+            let edge2 = Vec3::from(positions[i2]) - p0;
+            let uv0 = Vec2::from(uvs[i0]);
+            let duv1 = Vec2::from(uvs[i1]) - uv0;
+            let duv2 = Vec2::from(uvs[i2]) - uv0;
+
+            let det = duv1.x() * duv2.y() - duv2.x() * duv1.y();
+            if det.abs() < std::f32::EPSILON {
+                // degenerate uvs don't say anything about the tangent
+                continue;
+            }
+            let r = 1.0 / det;
+            let tangent = (edge1 * duv2.y() - edge2 * duv1.y()) * r;
+            let bitangent = (edge2 * duv1.x() - edge1 * duv2.x()) * r;
+            for &i in &[i0, i1, i2] {
+                tangents[i] += tangent;
+                bitangents[i] += bitangent;
+            }
+        }
+
+        Ok(normals
+            .iter()
+            .enumerate()
+            .map(|(i, normal)| {
+                let normal = Vec3::from(*normal);
+                // Gram-Schmidt so the tangent is perpendicular to the normal
+                let mut tangent = tangents[i] - normal * normal.dot(tangents[i]);
+                if tangent.length_squared() < std::f32::EPSILON {
+                    // no usable uvs around this vertex, any perpendicular will do
+                    tangent = if normal.x().abs() < 0.9 {
+                        Vec3::unit_x() - normal * normal.x()
+                    } else {
+                        Vec3::unit_y() - normal * normal.y()
+                    };
+                }
+                let tangent = tangent.normalize();
+                let handedness = if normal.cross(tangent).dot(bitangents[i]) < 0.0 {
+                    -1.0
+                } else {
+                    1.0
+                };
+                [tangent.x(), tangent.y(), tangent.z(), handedness]
+            })
+            .collect())
+    }
+
+    /// Moves every vertex by `transform`. Normals and tangents follow along, so non-uniform
+    /// scales keep the shading right, and a mirroring transform flips the triangles so they
+    /// still face out.
+    pub fn transform(&mut self, transform: &Mat4) {
+        let normal_matrix = transform.inverse().transpose();
+        let mirrored = transform.determinant() < 0.0;
+        if let Some(positions) = self.attribute_mut::<[f32; 3]>(VertexAttribute::POSITION) {
+            for position in positions.iter_mut() {
+                *position = (*transform * Vec3::from(*position).extend(1.0))
+                    .truncate()
+                    .into();
+            }
+        }
+        self.bounds.set(self.compute_bounds());
+        if let Some(normals) = self.attribute_mut::<[f32; 3]>(VertexAttribute::NORMAL) {
+            for normal in normals.iter_mut() {
+                *normal = (normal_matrix * Vec3::from(*normal).extend(0.0))
+                    .truncate()
+                    .normalize()
+                    .into();
+            }
+        }
+        if let Some(tangents) = self.attribute_mut::<[f32; 4]>(VertexAttribute::TANGENT) {
+            for tangent in tangents.iter_mut() {
+                let direction = (*transform * Vec4::new(tangent[0], tangent[1], tangent[2], 0.0))
+                    .truncate()
+                    .normalize();
+                let handedness = if mirrored { -tangent[3] } else { tangent[3] };
+                *tangent = [direction.x(), direction.y(), direction.z(), handedness];
+            }
+        }
+        if mirrored && self.primitive_topology == PrimitiveTopology::TriangleList {
+            let unindexed = self.indices.is_none();
+            let mut indices = self.triangle_indices().into_owned();
+            for triangle in indices.chunks_exact_mut(3) {
+                triangle.swap(1, 2);
+            }
+            self.indices = Some(indices);
+            if unindexed {
+                // reorders the vertices themselves and drops the indices again
+                self.duplicate_vertices();
+            } else {
+                // a range of vertices can't carry the new winding, so both buffers are rewritten
+                self.dirty_vertices.invalidate();
+                return;
+            }
+        }
+        self.dirty_vertices.mark(0..self.vertex_count());
+    }
+
+    /// Bakes `meshes` into one, e.g. to draw lots of small pieces at once. They need the same
+    /// topology and attributes, the result is only left without indices if none of them had any.
+    pub fn merge(meshes: &[Mesh]) -> Result<Mesh, MergeMeshesError> {
+        let first = match meshes.first() {
+            Some(first) => first,
+            None => return Ok(Mesh::new(PrimitiveTopology::TriangleList)),
+        };
+        let mut merged = Mesh::new(first.primitive_topology);
+        merged.attributes = first.attributes.clone();
+        let indexed = meshes.iter().any(|mesh| mesh.indices.is_some());
+        if indexed {
+            merged.indices = Some(first.triangle_indices().into_owned());
+        }
+
+        for mesh in meshes[1..].iter() {
+            if mesh.primitive_topology != merged.primitive_topology {
+                return Err(MergeMeshesError::MismatchedTopology(
+                    mesh.primitive_topology,
+                    merged.primitive_topology,
+                ));
+            }
+            if let Some(extra) = mesh
+                .attributes
+                .iter()
+                .find(|attribute| !merged.attributes.iter().any(|a| a.name == attribute.name))
+            {
+                return Err(MergeMeshesError::MismatchedAttribute(extra.name.clone()));
+            }
+
+            let offset = merged.vertex_count() as u32;
+            for attribute in merged.attributes.iter_mut() {
+                let values = mesh
+                    .attributes
+                    .iter()
+                    .find(|a| a.name == attribute.name)
+                    .map(|a| &a.values);
+                if !values.map_or(false, |values| attribute.values.extend(values)) {
+                    return Err(MergeMeshesError::MismatchedAttribute(
+                        attribute.name.clone(),
+                    ));
+                }
+            }
+            if let Some(indices) = &mut merged.indices {
+                indices.extend(mesh.triangle_indices().iter().map(|i| i + offset));
+            }
+        }
+        // from the parts' bounds, which are usually cached already
+        let bounds = meshes
+            .iter()
+            .map(|mesh| mesh.bounds())
+            .collect::<Option<Vec<_>>>();
+        merged
+            .bounds
+            .set(bounds.and_then(|bounds| Bounds::enclosing(&bounds)));
+        Ok(merged)
+    }
+
+    /// Replaces every value of the attribute `name` with what `f` makes of its vertex index
+    /// and old value. Does nothing if it's missing or doesn't hold `T`.
+    pub fn map_attribute<T: VertexAttributeType>(
+        &mut self,
+        name: &str,
+        mut f: impl FnMut(usize, &T) -> T,
+    ) {
+        if let Some(values) = self.attribute_mut::<T>(name) {
+            for (i, value) in values.iter_mut().enumerate() {
+                *value = f(i, value);
+            }
+        }
+    }
+
+    /// Recolours every vertex from its position and current colour, meshes without colours
+    /// start out white. Colours in a packed format are mapped as floats and packed back.
+    pub fn map_colors(&mut self, mut f: impl FnMut(Vec3, Color) -> Color) {
+        let positions = match self.attribute::<[f32; 3]>(VertexAttribute::POSITION) {
+            Some(positions) => positions.to_vec(),
+            None => return,
+        };
+        if self
+            .attributes
+            .iter()
+            .all(|attribute| attribute.name != VertexAttribute::COLOR)
+        {
+            self.set_color(Color::WHITE);
+        }
+        let values = match self
+            .attributes
+            .iter()
+            .find(|attribute| attribute.name == VertexAttribute::COLOR)
+        {
+            Some(attribute) => &attribute.values,
+            None => return,
+        };
+        let format = VertexFormat::from(values);
+        let mut colors = match values.convert(VertexFormat::Float4) {
+            Some(VertexAttributeValues::Float4(colors)) => colors,
+            _ => return,
+        };
+
+        for (color, position) in colors.iter_mut().zip(positions) {
+            let mapped = f(
+                Vec3::from(position),
+                Color::rgba(color[0], color[1], color[2], color[3]),
+            );
+            *color = [mapped.r, mapped.g, mapped.b, mapped.a];
+        }
+        let values = VertexAttributeValues::Float4(colors);
+        self.insert_attribute(VertexAttribute {
+            name: VertexAttribute::COLOR.into(),
+            values: values.convert(format).unwrap_or(values),
+        });
+    }
+
+    /// The bounds of the positions, computed the first time they're asked for after a change
+    /// and cached on the mesh. `None` without `Float3` positions or vertices.
+    pub fn bounds(&self) -> Option<Bounds> {
+        let mut cached = self.bounds.bounds.lock().unwrap();
+        *cached.get_or_insert_with(|| self.compute_bounds())
+    }
+
+    pub fn compute_bounds(&self) -> Option<Bounds> {
+        Some(Bounds {
+            aabb: self.compute_aabb()?,
+            sphere: self.compute_bounding_sphere()?,
+        })
+    }
+
+    /// The box around every position, `None` without `Float3` positions or vertices.
+    pub fn compute_aabb(&self) -> Option<Aabb> {
+        let positions = self.attribute::<[f32; 3]>(VertexAttribute::POSITION)?;
+        Aabb::from_points(positions.iter().map(|p| Vec3::from(*p)))
+    }
+
+    /// A sphere around every position, centered on the middle of `compute_aabb`. Not the
+    /// smallest possible, but tighter than the box's corners for round things like planets.
+    pub fn compute_bounding_sphere(&self) -> Option<BoundingSphere> {
+        let center = self.compute_aabb()?.center();
+        let positions = self.attribute::<[f32; 3]>(VertexAttribute::POSITION)?;
+        let radius = positions
+            .iter()
+            .map(|p| (Vec3::from(*p) - center).length_squared())
+            .fold(0.0, f32::max)
+            .sqrt();
+        Some(BoundingSphere { center, radius })
+    }
+
+    /// Gives every vertex the same colour, adding the colour attribute if it's missing.
+    pub fn set_color(&mut self, color: Color) {
+        let colors = vec![[color.r, color.g, color.b, color.a]; self.vertex_count()];
+        self.insert_attribute(VertexAttribute::color(colors));
+    }
+}
+
+/// Generation for some primitive shape meshes.
+pub mod shape {
+    use super::{Mesh, VertexAttribute};
+    use crate::pipeline::PrimitiveTopology;
+    use bevy_math::*;
+    use bevy_utils::HashMap;
+    use hexasphere::Hexasphere;
+
+    /// A cube.
+    pub struct Cube {
+        /// Half the side length of the cube.
+        pub size: f32,
+    }
+
+    impl Default for Cube {
+        fn default() -> Self {
+            Cube { size: 1.0 }
+        }
+    }
+
+    impl From<Cube> for Mesh {
+        fn from(cube: Cube) -> Self {
+            let size = cube.size;
+            let vertices = &[
+                // top (0., 0., size)
+                ([-size, -size, size], [0., 0., size], [0., 0.], [1.0, 1.0, 1.0, 1.0]),
+                ([size, -size, size], [0., 0., size], [size, 0.], [1.0, 1.0, 1.0, 1.0]),
+                ([size, size, size], [0., 0., size], [size, size], [1.0, 1.0, 1.0, 1.0]),
+                ([-size, size, size], [0., 0., size], [0., size], [1.0, 1.0, 1.0, 1.0]),
+                // bottom (0., 0., -size)
+                ([-size, size, -size], [0., 0., -size], [size, 0.], [1.0, 1.0, 1.0, 1.0]),
+                ([size, size, -size], [0., 0., -size], [0., 0.], [1.0, 1.0, 1.0, 1.0]),
+                ([size, -size, -size], [0., 0., -size], [0., size], [1.0, 1.0, 1.0, 1.0]),
+                ([-size, -size, -size], [0., 0., -size], [size, size], [1.0, 1.0, 1.0, 1.0]),
+                // right (size, 0., 0.)
+                ([size, -size, -size], [size, 0., 0.], [0., 0.], [1.0, 1.0, 1.0, 1.0]),
+                ([size, size, -size], [size, 0., 0.], [size, 0.], [1.0, 1.0, 1.0, 1.0]),
+                ([size, size, size], [size, 0., 0.], [size, size], [1.0, 1.0, 1.0, 1.0]),
+                ([size, -size, size], [size, 0., 0.], [0., size], [1.0, 1.0, 1.0, 1.0]),
+                // left (-size, 0., 0.)
+                ([-size, -size, size], [-size, 0., 0.], [size, 0.], [1.0, 1.0, 1.0, 1.0]),
+                ([-size, size, size], [-size, 0., 0.], [0., 0.], [1.0, 1.0, 1.0, 1.0]),
+                ([-size, size, -size], [-size, 0., 0.], [0., size], [1.0, 1.0, 1.0, 1.0]),
+                ([-size, -size, -size], [-size, 0., 0.], [size, size], [1.0, 1.0, 1.0, 1.0]),
+                // front (0., size, 0.)
+                ([size, size, -size], [0., size, 0.], [size, 0.], [1.0, 1.0, 1.0, 1.0]),
+                ([-size, size, -size], [0., size, 0.], [0., 0.], [1.0, 1.0, 1.0, 1.0]),
+                ([-size, size, size], [0., size, 0.], [0., size], [1.0, 1.0, 1.0, 1.0]),
+                ([size, size, size], [0., size, 0.], [size, size], [1.0, 1.0, 1.0, 1.0]),
+                // back (0., -size, 0.)
+                ([size, -size, size], [0., -size, 0.], [0., 0.], [1.0, 1.0, 1.0, 1.0]),
+                ([-size, -size, size], [0., -size, 0.], [size, 0.], [1.0, 1.0, 1.0, 1.0]),
+                ([-size, -size, -size], [0., -size, 0.], [size, size], [1.0, 1.0, 1.0, 1.0]),
+                ([size, -size, -size], [0., -size, 0.], [0., size], [1.0, 1.0, 1.0, 1.0]),
+            ];
+
+            let mut positions = Vec::new();
+            let mut normals = Vec::new();
+            let mut uvs = Vec::new();
+            let mut colors = Vec::new();
+            for (position, normal, uv, color) in vertices.iter() {
+                positions.push(*position);
+                normals.push(*normal);
+                uvs.push(*uv);
+                colors.push(*color);
+            }
+
+            let indices = vec![
+                0, 1, 2, 2, 3, 0, // top
+                4, 5, 6, 6, 7, 4, // bottom
+                8, 9, 10, 10, 11, 8, // right
+                12, 13, 14, 14, 15, 12, // left
+                16, 17, 18, 18, 19, 16, // front
+                20, 21, 22, 22, 23, 20, // back
+            ];
+
+            Mesh {
+                primitive_topology: PrimitiveTopology::TriangleList,
+                attributes: vec![
+                    VertexAttribute::position(positions),
+                    VertexAttribute::normal(normals),
+                    VertexAttribute::uv(uvs),
+                    VertexAttribute::color(colors),
+                ],
+                indices: Some(indices),
+                dirty_vertices: Default::default(),
+                bounds: Default::default(),
+            }
+        }
+    }
+
+    /// A rectangle on the XY plane.
+    pub struct Quad {
+        /// Full width and height of the rectangle.
+        pub size: Vec2,
+        /// Flips the texture coords of the resulting vertices.
+        pub flip: bool,
+    }
+
+    impl Quad {
+        pub fn new(size: Vec2) -> Self {
+            Self { size, flip: false }
+        }
+
+        pub fn flipped(size: Vec2) -> Self {
             Self { size, flip: true }
         }
     }
@@ -399,6 +1473,8 @@ pub mod shape {
                     VertexAttribute::color(colors),
                 ],
                 indices: Some(indices),
+                dirty_vertices: Default::default(),
+                bounds: Default::default(),
             }
         }
     }
@@ -442,6 +1518,73 @@ pub mod shape {
                     VertexAttribute::color(colors),
                 ],
                 indices: Some(indices),
+                dirty_vertices: Default::default(),
+                bounds: Default::default(),
+            }
+        }
+    }
+
+    /// A flat ring on the XZ plane, facing up.
+    pub struct Annulus {
+        /// The radius of the inner edge.
+        pub inner_radius: f32,
+        /// The radius of the outer edge.
+        pub outer_radius: f32,
+        /// The number of segments around the ring.
+        pub segments: usize,
+    }
+
+    impl Default for Annulus {
+        fn default() -> Self {
+            Annulus {
+                inner_radius: 0.5,
+                outer_radius: 1.0,
+                segments: 64,
+            }
+        }
+    }
+
+    impl From<Annulus> for Mesh {
+        fn from(annulus: Annulus) -> Self {
+            let segments = annulus.segments.max(3);
+
+            let mut positions = Vec::with_capacity((segments + 1) * 2);
+            let mut normals = Vec::with_capacity((segments + 1) * 2);
+            let mut uvs = Vec::with_capacity((segments + 1) * 2);
+            let mut colors = Vec::with_capacity((segments + 1) * 2);
+            // the first column is repeated at the end so v can wrap from 0 to 1
+            for i in 0..=segments {
+                let v = i as f32 / segments as f32;
+                let (sin, cos) = (v * std::f32::consts::PI * 2.0).sin_cos();
+                for (radius, u) in &[(annulus.inner_radius, 0.0), (annulus.outer_radius, 1.0)] {
+                    positions.push([cos * radius, 0.0, sin * radius]);
+                    normals.push([0.0, 1.0, 0.0]);
+                    // u runs from the inner edge to the outer edge
+                    uvs.push([*u, v]);
+                    colors.push([1.0, 1.0, 1.0, 1.0]);
+                }
+            }
+
+            let mut indices = Vec::with_capacity(segments * 6);
+            for i in 0..segments as u32 {
+                let inner = i * 2;
+                let outer = inner + 1;
+                let next_inner = inner + 2;
+                let next_outer = inner + 3;
+                indices.extend_from_slice(&[inner, next_outer, outer, inner, next_inner, next_outer]);
+            }
+
+            Mesh {
+                primitive_topology: PrimitiveTopology::TriangleList,
+                attributes: vec![
+                    VertexAttribute::position(positions),
+                    VertexAttribute::normal(normals),
+                    VertexAttribute::uv(uvs),
+                    VertexAttribute::color(colors),
+                ],
+                indices: Some(indices),
+                dirty_vertices: Default::default(),
+                bounds: Default::default(),
             }
         }
     }
@@ -452,6 +1595,9 @@ pub mod shape {
         pub radius: f32,
         /// The number of subdivisions applied.
         pub subdivisions: usize,
+        /// Use equirectangular uvs around +Y, duplicating the vertices on the seam and at the poles
+        /// so the texture doesn't smear.
+        pub split_seam: bool,
     }
 
     impl Default for Icosphere {
@@ -459,21 +1605,13 @@ pub mod shape {
             Self {
                 radius: 1.0,
                 subdivisions: 5,
+                split_seam: false,
             }
         }
     }
 
     impl From<Icosphere> for Mesh {
         fn from(sphere: Icosphere) -> Self {
-            if sphere.subdivisions >= 80 {
-                let temp_sphere = Hexasphere::new(sphere.subdivisions, |_| ());
-
-                panic!(
-                    "Cannot create an icosphere of {} subdivisions due to there being too many vertices being generated: {} (Limited to 65535 vertices or 79 subdivisions)",
-                    sphere.subdivisions,
-                    temp_sphere.raw_points().len()
-                );
-            }
             let hexasphere = Hexasphere::new(sphere.subdivisions, |point| {
                 let inclination = point.z().acos();
                 let azumith = point.y().atan2(point.x());
@@ -486,23 +1624,23 @@ pub mod shape {
 
             let raw_points = hexasphere.raw_points();
 
-            let points = raw_points
+            let mut points = raw_points
                 .iter()
                 .map(|&p| (p * sphere.radius).into())
                 .collect::<Vec<[f32; 3]>>();
 
-            let normals = raw_points
+            let mut normals = raw_points
                 .iter()
                 .copied()
                 .map(Into::into)
                 .collect::<Vec<[f32; 3]>>();
 
-            let colors = raw_points
+            let mut colors = raw_points
                 .iter()
                 .map(|_| [1.0, 1.0, 1.0, 1.0])
                 .collect::<Vec<[f32; 4]>>();
 
-            let uvs = hexasphere.raw_data().to_owned();
+            let mut uvs = hexasphere.raw_data().to_owned();
 
             let mut indices = Vec::with_capacity(hexasphere.indices_per_main_triangle() * 20);
 
@@ -510,6 +1648,10 @@ pub mod shape {
                 hexasphere.get_indices(i, &mut indices);
             }
 
+            if sphere.split_seam {
+                uvs = split_uv_seam(&mut points, &mut normals, &mut colors, &mut indices);
+            }
+
             Mesh {
                 primitive_topology: PrimitiveTopology::TriangleList,
                 attributes: vec![
@@ -519,26 +1661,633 @@ pub mod shape {
                     VertexAttribute::color(colors),
                 ],
                 indices: Some(indices),
+                dirty_vertices: Default::default(),
+                bounds: Default::default(),
+            }
+        }
+    }
+
+    fn is_pole(normal: [f32; 3]) -> bool {
+        normal[1].abs() > 1.0 - 1e-5
+    }
+
+    fn duplicate_vertex(
+        i: usize,
+        uv: [f32; 2],
+        points: &mut Vec<[f32; 3]>,
+        normals: &mut Vec<[f32; 3]>,
+        colors: &mut Vec<[f32; 4]>,
+        uvs: &mut Vec<[f32; 2]>,
+    ) -> u32 {
+        points.push(points[i]);
+        normals.push(normals[i]);
+        colors.push(colors[i]);
+        uvs.push(uv);
+        (uvs.len() - 1) as u32
+    }
+
+    /// Equirectangular uvs for a unit sphere around +Y: u follows the longitude and v runs from
+    /// the north pole (0) to the south pole (1). Triangles crossing the antimeridian get their
+    /// low side duplicated with u + 1, and every triangle touching a pole gets its own pole
+    /// vertex with u in the middle of the triangle.
+    fn split_uv_seam(
+        points: &mut Vec<[f32; 3]>,
+        normals: &mut Vec<[f32; 3]>,
+        colors: &mut Vec<[f32; 4]>,
+        indices: &mut Vec<u32>,
+    ) -> Vec<[f32; 2]> {
+        let mut uvs = normals
+            .iter()
+            .map(|n| {
+                let u = (-n[2]).atan2(n[0]) / (2.0 * std::f32::consts::PI) + 0.5;
+                let v = n[1].max(-1.0).min(1.0).acos() / std::f32::consts::PI;
+                [u, v]
+            })
+            .collect::<Vec<[f32; 2]>>();
+
+        let mut wrapped = HashMap::<u32, u32>::default();
+        for t in 0..indices.len() / 3 {
+            let triangle = t * 3..t * 3 + 3;
+
+            let corners = indices[triangle.clone()]
+                .iter()
+                .filter(|&&i| !is_pole(normals[i as usize]))
+                .map(|&i| uvs[i as usize][0])
+                .collect::<Vec<f32>>();
+            let max = corners.iter().cloned().fold(std::f32::MIN, f32::max);
+            let min = corners.iter().cloned().fold(std::f32::MAX, f32::min);
+            if max - min > 0.5 {
+                for corner in triangle.clone() {
+                    let i = indices[corner];
+                    if !is_pole(normals[i as usize]) && uvs[i as usize][0] < 0.5 {
+                        indices[corner] = match wrapped.get(&i) {
+                            Some(&w) => w,
+                            None => {
+                                let uv = uvs[i as usize];
+                                let w = duplicate_vertex(
+                                    i as usize,
+                                    [uv[0] + 1.0, uv[1]],
+                                    points,
+                                    normals,
+                                    colors,
+                                    &mut uvs,
+                                );
+                                wrapped.insert(i, w);
+                                w
+                            }
+                        };
+                    }
+                }
+            }
+
+            for corner in triangle.clone() {
+                let i = indices[corner] as usize;
+                if is_pole(normals[i]) {
+                    let others = triangle
+                        .clone()
+                        .filter(|&c| c != corner)
+                        .map(|c| uvs[indices[c] as usize][0])
+                        .collect::<Vec<f32>>();
+                    let u = others.iter().sum::<f32>() / others.len() as f32;
+                    let v = uvs[i][1];
+                    indices[corner] = duplicate_vertex(i, [u, v], points, normals, colors, &mut uvs);
+                }
+            }
+        }
+
+        uvs
+    }
+
+    /// A sphere made of latitude rings and longitude sectors. u follows longitude and v
+    /// latitude, so equirectangular textures map onto it without distortion at the seam.
+    pub struct UVSphere {
+        /// The radius of the sphere.
+        pub radius: f32,
+        /// The number of longitude segments.
+        pub sectors: usize,
+        /// The number of latitude segments, from pole to pole.
+        pub stacks: usize,
+    }
+
+    impl Default for UVSphere {
+        fn default() -> Self {
+            UVSphere {
+                radius: 1.0,
+                sectors: 36,
+                stacks: 18,
+            }
+        }
+    }
+
+    impl From<UVSphere> for Mesh {
+        fn from(sphere: UVSphere) -> Self {
+            let stacks = sphere.stacks.max(2);
+            let profile = (0..=stacks)
+                .map(|j| {
+                    let normal = meridian(
+                        std::f32::consts::FRAC_PI_2
+                            - std::f32::consts::PI * j as f32 / stacks as f32,
+                    );
+                    (normal * sphere.radius, normal)
+                })
+                .collect::<Vec<_>>();
+
+            let mut builder = ShapeBuilder::default();
+            builder.lathe(&profile, sphere.sectors);
+            builder.build()
+        }
+    }
+
+    /// A ring shaped tube around the Y axis.
+    pub struct Torus {
+        /// The distance from the center to the middle of the tube.
+        pub radius: f32,
+        /// The radius of the tube.
+        pub tube_radius: f32,
+        /// The number of segments around the Y axis.
+        pub segments: usize,
+        /// The number of segments around the tube.
+        pub tube_segments: usize,
+    }
+
+    impl Default for Torus {
+        fn default() -> Self {
+            Torus {
+                radius: 1.0,
+                tube_radius: 0.25,
+                segments: 32,
+                tube_segments: 16,
+            }
+        }
+    }
+
+    impl From<Torus> for Mesh {
+        fn from(torus: Torus) -> Self {
+            let tube_segments = torus.tube_segments.max(3);
+            // starts at the top of the tube and heads outward first
+            let profile = (0..=tube_segments)
+                .map(|j| {
+                    let angle = std::f32::consts::FRAC_PI_2
+                        - std::f32::consts::PI * 2.0 * j as f32 / tube_segments as f32;
+                    let normal = Vec2::new(angle.cos(), angle.sin());
+                    (
+                        Vec2::new(torus.radius, 0.0) + normal * torus.tube_radius,
+                        normal,
+                    )
+                })
+                .collect::<Vec<_>>();
+
+            let mut builder = ShapeBuilder::default();
+            builder.lathe(&profile, torus.segments);
+            builder.build()
+        }
+    }
+
+    /// A capped cylinder around the Y axis, centered on the origin.
+    pub struct Cylinder {
+        /// The radius of the cylinder.
+        pub radius: f32,
+        /// The total height of the cylinder.
+        pub height: f32,
+        /// The number of segments around the Y axis.
+        pub segments: usize,
+    }
+
+    impl Default for Cylinder {
+        fn default() -> Self {
+            Cylinder {
+                radius: 0.5,
+                height: 1.0,
+                segments: 32,
+            }
+        }
+    }
+
+    impl From<Cylinder> for Mesh {
+        fn from(cylinder: Cylinder) -> Self {
+            let (radius, half_height) = (cylinder.radius, cylinder.height / 2.0);
+            let outward = Vec2::new(1.0, 0.0);
+
+            let mut builder = ShapeBuilder::default();
+            builder.lathe(
+                &[
+                    (Vec2::new(radius, half_height), outward),
+                    (Vec2::new(radius, -half_height), outward),
+                ],
+                cylinder.segments,
+            );
+            builder.cap(radius, half_height, cylinder.segments, true);
+            builder.cap(radius, -half_height, cylinder.segments, false);
+            builder.build()
+        }
+    }
+
+    /// A cylinder with hemispheres on both ends, around the Y axis and centered on the origin.
+    pub struct Capsule {
+        /// The radius of the cylinder and the hemispheres.
+        pub radius: f32,
+        /// The height of the cylinder between the hemispheres.
+        pub depth: f32,
+        /// The number of segments around the Y axis.
+        pub segments: usize,
+        /// The number of latitude segments in each hemisphere.
+        pub rings: usize,
+    }
+
+    impl Default for Capsule {
+        fn default() -> Self {
+            Capsule {
+                radius: 0.5,
+                depth: 1.0,
+                segments: 32,
+                rings: 8,
+            }
+        }
+    }
+
+    impl From<Capsule> for Mesh {
+        fn from(capsule: Capsule) -> Self {
+            let rings = capsule.rings.max(1);
+            let half_depth = capsule.depth / 2.0;
+            // the top hemisphere down to its equator, then the bottom one from its equator
+            let profile = (0..=rings)
+                .map(|j| (half_depth, 1.0 - j as f32 / rings as f32))
+                .chain((0..=rings).map(|j| (-half_depth, -(j as f32) / rings as f32)))
+                .map(|(center, t)| {
+                    let normal = meridian(t * std::f32::consts::FRAC_PI_2);
+                    (Vec2::new(0.0, center) + normal * capsule.radius, normal)
+                })
+                .collect::<Vec<_>>();
+
+            let mut builder = ShapeBuilder::default();
+            builder.lathe(&profile, capsule.segments);
+            builder.build()
+        }
+    }
+
+    /// A cone around the Y axis with its tip up, centered on the origin.
+    pub struct Cone {
+        /// The radius of the base.
+        pub radius: f32,
+        /// The height from the base to the tip.
+        pub height: f32,
+        /// The number of segments around the Y axis.
+        pub segments: usize,
+    }
+
+    impl Default for Cone {
+        fn default() -> Self {
+            Cone {
+                radius: 0.5,
+                height: 1.0,
+                segments: 32,
+            }
+        }
+    }
+
+    impl From<Cone> for Mesh {
+        fn from(cone: Cone) -> Self {
+            let half_height = cone.height / 2.0;
+            let slant = Vec2::new(cone.height, cone.radius).normalize();
+
+            let mut builder = ShapeBuilder::default();
+            // every sector gets its own tip vertex, so the tip is shaded like the slope
+            builder.lathe(
+                &[
+                    (Vec2::new(0.0, half_height), slant),
+                    (Vec2::new(cone.radius, -half_height), slant),
+                ],
+                cone.segments,
+            );
+            builder.cap(cone.radius, -half_height, cone.segments, false);
+            builder.build()
+        }
+    }
+
+    /// Unit vector at `latitude` as `(radius, height)`, exactly on the axis at the poles so
+    /// `ShapeBuilder::lathe` can tell.
+    fn meridian(latitude: f32) -> Vec2 {
+        if latitude.abs() >= std::f32::consts::FRAC_PI_2 {
+            Vec2::new(0.0, latitude.signum())
+        } else {
+            Vec2::new(latitude.cos(), latitude.sin())
+        }
+    }
+
+    /// Collects the vertices of the round shapes, every one of them is white.
+    #[derive(Default)]
+    struct ShapeBuilder {
+        positions: Vec<[f32; 3]>,
+        normals: Vec<[f32; 3]>,
+        uvs: Vec<[f32; 2]>,
+        indices: Vec<u32>,
+    }
+
+    impl ShapeBuilder {
+        /// Sweeps `profile` around the Y axis. Each entry is a point as `(radius, height)` and
+        /// its normal in the same plane, listed with the outside on the right, e.g. top to
+        /// bottom for a sphere. u goes around counter-clockwise seen from above, starting at
+        /// +X, and v follows the length of the profile.
+        fn lathe(&mut self, profile: &[(Vec2, Vec2)], segments: usize) {
+            let segments = segments.max(3);
+            let mut lengths = vec![0.0];
+            for pair in profile.windows(2) {
+                lengths.push(lengths.last().unwrap() + (pair[1].0 - pair[0].0).length());
+            }
+            let total = lengths
+                .last()
+                .cloned()
+                .unwrap_or(0.0)
+                .max(std::f32::EPSILON);
+
+            let first = self.positions.len() as u32;
+            for ((point, normal), length) in profile.iter().zip(lengths.iter()) {
+                // the first column is repeated at the end so u can wrap from 0 to 1
+                for i in 0..=segments {
+                    let u = i as f32 / segments as f32;
+                    let (sin, cos) = (u * std::f32::consts::PI * 2.0).sin_cos();
+                    self.positions
+                        .push([point.x() * cos, point.y(), -point.x() * sin]);
+                    self.normals
+                        .push([normal.x() * cos, normal.y(), -normal.x() * sin]);
+                    self.uvs.push([u, length / total]);
+                }
+            }
+
+            let columns = segments as u32 + 1;
+            for (j, pair) in profile.windows(2).enumerate() {
+                for i in 0..segments as u32 {
+                    let upper = first + j as u32 * columns + i;
+                    let lower = upper + columns;
+                    // triangles that would collapse onto the axis are left out
+                    if pair[1].0.x() != 0.0 {
+                        self.indices.extend_from_slice(&[upper, lower, lower + 1]);
+                    }
+                    if pair[0].0.x() != 0.0 {
+                        self.indices
+                            .extend_from_slice(&[upper, lower + 1, upper + 1]);
+                    }
+                }
+            }
+        }
+
+        /// A disc at `height` closing off a lathed shape, facing up or down.
+        fn cap(&mut self, radius: f32, height: f32, segments: usize, up: bool) {
+            let segments = segments.max(3);
+            let normal = if up { 1.0 } else { -1.0 };
+            let center = self.positions.len() as u32;
+            self.positions.push([0.0, height, 0.0]);
+            self.normals.push([0.0, normal, 0.0]);
+            self.uvs.push([0.5, 0.5]);
+            for i in 0..=segments {
+                let (sin, cos) =
+                    (i as f32 / segments as f32 * std::f32::consts::PI * 2.0).sin_cos();
+                self.positions.push([radius * cos, height, -radius * sin]);
+                self.normals.push([0.0, normal, 0.0]);
+                self.uvs.push([0.5 + cos * 0.5, 0.5 - sin * normal * 0.5]);
+            }
+            for i in 0..segments as u32 {
+                let (current, next) = (center + 1 + i, center + 2 + i);
+                if up {
+                    self.indices.extend_from_slice(&[center, current, next]);
+                } else {
+                    self.indices.extend_from_slice(&[center, next, current]);
+                }
+            }
+        }
+
+        fn build(self) -> Mesh {
+            let colors = vec![[1.0, 1.0, 1.0, 1.0]; self.positions.len()];
+            Mesh {
+                primitive_topology: PrimitiveTopology::TriangleList,
+                attributes: vec![
+                    VertexAttribute::position(self.positions),
+                    VertexAttribute::normal(self.normals),
+                    VertexAttribute::uv(self.uvs),
+                    VertexAttribute::color(colors),
+                ],
+                indices: Some(self.indices),
+                dirty_vertices: Default::default(),
+                bounds: Default::default(),
             }
         }
     }
 }
 
-fn remove_current_mesh_resources(
+/// The mesh attribute a vertex layout attribute is packed from. Layout attributes follow the
+/// shader naming convention `<layout>_<name>` and read the mesh's `Vertex_<name>`, so a
+/// `PlanetVertex_Elevation` shader input is filled from `Vertex_Elevation`.
+pub fn mesh_attribute_name<'a>(layout_name: &str, attribute_name: &'a str) -> Cow<'a, str> {
+    if layout_name == "Vertex" {
+        return Cow::Borrowed(attribute_name);
+    }
+    match attribute_name.strip_prefix(layout_name) {
+        Some(name) if name.starts_with('_') => Cow::Owned(format!("Vertex{}", name)),
+        _ => Cow::Borrowed(attribute_name),
+    }
+}
+
+/// A copy out of a staging buffer into a mesh buffer, see `MeshStagingNode`.
+struct StagedWrite {
+    staging: BufferId,
+    destination: BufferId,
+    offset: u64,
+    size: u64,
+}
+
+/// Mesh buffer writes queued by `mesh_resource_provider_system` for `MeshStagingNode`.
+#[derive(Default)]
+pub struct MeshStagingBuffers {
+    writes: Vec<StagedWrite>,
+    /// staging buffers copied from last frame, freed once those copies have been submitted
+    retired: Vec<BufferId>,
+}
+
+impl MeshStagingBuffers {
+    fn write(
+        &mut self,
+        render_resource_context: &dyn RenderResourceContext,
+        destination: BufferId,
+        offset: usize,
+        data: &[u8],
+    ) {
+        let staging = render_resource_context.create_buffer_mapped(
+            BufferInfo {
+                size: data.len(),
+                buffer_usage: BufferUsage::COPY_SRC | BufferUsage::MAP_WRITE,
+                ..Default::default()
+            },
+            &mut |mapped, _renderer| mapped.copy_from_slice(data),
+        );
+        self.writes.push(StagedWrite {
+            staging,
+            destination,
+            offset: offset as u64,
+            size: data.len() as u64,
+        });
+    }
+}
+
+/// Copies the queued mesh writes into place, it needs an edge to `base::node::MAIN_PASS`.
+#[derive(Default)]
+pub struct MeshStagingNode;
+
+impl Node for MeshStagingNode {
+    fn update(
+        &mut self,
+        _world: &World,
+        resources: &Resources,
+        render_context: &mut dyn RenderContext,
+        _input: &ResourceSlots,
+        _output: &mut ResourceSlots,
+    ) {
+        let mut staging_buffers = resources.get_mut::<MeshStagingBuffers>().unwrap();
+        let staging_buffers = &mut *staging_buffers;
+        for buffer in staging_buffers.retired.drain(..) {
+            render_context.resources().remove_buffer(buffer);
+        }
+        for write in staging_buffers.writes.drain(..) {
+            render_context.copy_buffer_to_buffer(
+                write.staging,
+                0,
+                write.destination,
+                write.offset,
+                write.size,
+            );
+            staging_buffers.retired.push(write.staging);
+        }
+    }
+}
+
+fn get_buffer(
+    render_resource_context: &dyn RenderResourceContext,
+    handle: Handle<Mesh>,
+    index: usize,
+) -> Option<BufferId> {
+    match render_resource_context.get_asset_resource(handle, index) {
+        Some(RenderResourceId::Buffer(buffer)) => Some(buffer),
+        _ => None,
+    }
+}
+
+fn remove_mesh_buffer(
     render_resource_context: &dyn RenderResourceContext,
     handle: Handle<Mesh>,
+    index: usize,
 ) {
-    if let Some(RenderResourceId::Buffer(buffer)) =
-        render_resource_context.get_asset_resource(handle, VERTEX_BUFFER_ASSET_INDEX)
-    {
+    if let Some(buffer) = get_buffer(render_resource_context, handle, index) {
         render_resource_context.remove_buffer(buffer);
-        render_resource_context.remove_asset_resource(handle, VERTEX_BUFFER_ASSET_INDEX);
+        render_resource_context.remove_asset_resource(handle, index);
     }
-    if let Some(RenderResourceId::Buffer(buffer)) =
-        render_resource_context.get_asset_resource(handle, INDEX_BUFFER_ASSET_INDEX)
-    {
-        render_resource_context.remove_buffer(buffer);
-        render_resource_context.remove_asset_resource(handle, INDEX_BUFFER_ASSET_INDEX);
+}
+
+fn remove_current_mesh_resources(
+    render_resource_context: &dyn RenderResourceContext,
+    handle: Handle<Mesh>,
+    custom_layouts: &HashMap<Cow<'static, str>, usize>,
+) {
+    for index in custom_layouts.values() {
+        remove_mesh_buffer(render_resource_context, handle, *index);
+    }
+    remove_mesh_buffer(render_resource_context, handle, VERTEX_BUFFER_ASSET_INDEX);
+    remove_mesh_buffer(render_resource_context, handle, INDEX_BUFFER_ASSET_INDEX);
+}
+
+/// Writes a modified mesh's vertices into its existing buffer at `index` through a staging
+/// buffer, only the `dirty_vertices` if there are any. `false` if there's no buffer or the
+/// vertices don't fit in it anymore.
+fn write_vertex_buffer(
+    render_resource_context: &dyn RenderResourceContext,
+    staging_buffers: &mut MeshStagingBuffers,
+    handle: Handle<Mesh>,
+    index: usize,
+    stride: usize,
+    vertex_bytes: &[u8],
+    dirty_vertices: Option<Range<usize>>,
+) -> bool {
+    let vertex_buffer = match get_buffer(render_resource_context, handle, index) {
+        Some(buffer) => buffer,
+        None => return false,
+    };
+    let same_size = render_resource_context
+        .get_buffer_info(vertex_buffer)
+        .map_or(false, |info| info.size == vertex_bytes.len());
+    // copies have to be 4 byte aligned
+    if !same_size || stride == 0 || stride % 4 != 0 {
+        return false;
+    }
+
+    let vertices = dirty_vertices
+        .filter(|vertices| vertices.end * stride <= vertex_bytes.len())
+        .unwrap_or(0..vertex_bytes.len() / stride);
+    staging_buffers.write(
+        render_resource_context,
+        vertex_buffer,
+        vertices.start * stride,
+        &vertex_bytes[vertices.start * stride..vertices.end * stride],
+    );
+    true
+}
+
+/// Packs `mesh` into `descriptor`'s layout, `None` after logging why it can't be, e.g. an
+/// attribute whose format has no conversion to the layout's.
+fn pack_vertex_buffer(
+    handle: Handle<Mesh>,
+    mesh: &Mesh,
+    descriptor: &VertexBufferDescriptor,
+) -> Option<Vec<u8>> {
+    match mesh.get_vertex_buffer_bytes(descriptor, true) {
+        Ok(vertex_bytes) => Some(vertex_bytes),
+        Err(error) => {
+            log::error!(
+                "Mesh {:?} can't be packed into vertex layout {} and won't be drawn with it: {}",
+                handle,
+                descriptor.name,
+                error
+            );
+            None
+        }
+    }
+}
+
+fn hash_bytes(bytes: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    bytes.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Recreates the mesh's index buffer if `index_bytes` differ from what was uploaded last.
+/// `uploaded_indices` holds a hash of those per mesh, meshes without indices aren't in it.
+fn update_index_buffer(
+    render_resource_context: &dyn RenderResourceContext,
+    uploaded_indices: &mut HashMap<Handle<Mesh>, u64>,
+    handle: Handle<Mesh>,
+    index_bytes: Option<&[u8]>,
+) {
+    let hash = index_bytes.map(hash_bytes);
+    let buffer_exists =
+        get_buffer(render_resource_context, handle, INDEX_BUFFER_ASSET_INDEX).is_some();
+    if buffer_exists == hash.is_some() && uploaded_indices.get(&handle).copied() == hash {
+        return;
+    }
+
+    // index buffers are rarely a multiple of 4 bytes, so they're recreated instead of copied
+    remove_mesh_buffer(render_resource_context, handle, INDEX_BUFFER_ASSET_INDEX);
+    uploaded_indices.remove(&handle);
+    if let (Some(index_bytes), Some(hash)) = (index_bytes, hash) {
+        let index_buffer = render_resource_context.create_buffer_with_data(
+            BufferInfo {
+                buffer_usage: BufferUsage::INDEX,
+                ..Default::default()
+            },
+            index_bytes,
+        );
+        render_resource_context.set_asset_resource(
+            handle,
+            RenderResourceId::Buffer(index_buffer),
+            INDEX_BUFFER_ASSET_INDEX,
+        );
+        uploaded_indices.insert(handle, hash);
     }
 }
 
@@ -546,6 +2295,20 @@ fn remove_current_mesh_resources(
 pub struct MeshResourceProviderState {
     mesh_event_reader: EventReader<AssetEvent<Mesh>>,
     vertex_buffer_descriptor: Option<&'static VertexBufferDescriptor>,
+    /// asset index of every vertex layout besides `Vertex` a pipeline has asked for
+    custom_layouts: HashMap<Cow<'static, str>, usize>,
+    /// see `update_index_buffer`
+    uploaded_indices: HashMap<Handle<Mesh>, u64>,
+    /// meshes and the custom layouts they failed to pack into, not tried again until the mesh
+    /// changes
+    unpackable: HashSet<(Handle<Mesh>, usize)>,
+}
+
+impl MeshResourceProviderState {
+    fn custom_layout_index(&mut self, name: &Cow<'static, str>) -> usize {
+        let next = CUSTOM_VERTEX_BUFFER_ASSET_INDEX + self.custom_layouts.len();
+        *self.custom_layouts.entry(name.clone()).or_insert(next)
+    }
 }
 
 pub fn mesh_resource_provider_system(
@@ -553,13 +2316,17 @@ pub fn mesh_resource_provider_system(
     render_resource_context: Res<Box<dyn RenderResourceContext>>,
     meshes: Res<Assets<Mesh>>,
     mut vertex_buffer_descriptors: ResMut<VertexBufferDescriptors>,
+    mut staging_buffers: ResMut<MeshStagingBuffers>,
+    pipeline_compiler: Res<PipelineCompiler>,
+    pipelines: Res<Assets<PipelineDescriptor>>,
     mesh_events: Res<Events<AssetEvent<Mesh>>>,
     mut query: Query<(&Handle<Mesh>, &mut RenderPipelines)>,
 ) {
     let vertex_buffer_descriptor = match state.vertex_buffer_descriptor {
         Some(value) => value,
         None => {
-            // TODO: allow pipelines to specialize on vertex_buffer_descriptor and index_format
+            // the default layout, pipelines can add their own to VertexBufferDescriptors
+            // and name their vertex inputs after it, see `mesh_attribute_name`
             let vertex_buffer_descriptor = Vertex::as_vertex_buffer_descriptor();
             vertex_buffer_descriptors.set(vertex_buffer_descriptor.clone());
             state.vertex_buffer_descriptor = Some(vertex_buffer_descriptor);
@@ -568,6 +2335,7 @@ pub fn mesh_resource_provider_system(
     };
     let mut changed_meshes = HashSet::<Handle<Mesh>>::default();
     let render_resource_context = &**render_resource_context;
+    let state = &mut *state;
     for event in state.mesh_event_reader.iter(&mesh_events) {
         match event {
             AssetEvent::Created { handle } => {
@@ -575,10 +2343,15 @@ pub fn mesh_resource_provider_system(
             }
             AssetEvent::Modified { handle } => {
                 changed_meshes.insert(*handle);
-                remove_current_mesh_resources(render_resource_context, *handle);
             }
             AssetEvent::Removed { handle } => {
-                remove_current_mesh_resources(render_resource_context, *handle);
+                remove_current_mesh_resources(
+                    render_resource_context,
+                    *handle,
+                    &state.custom_layouts,
+                );
+                state.uploaded_indices.remove(handle);
+                state.unpackable.retain(|(mesh, _)| mesh != handle);
                 // if mesh was modified and removed in the same update, ignore the modification
                 // events are ordered so future modification events are ok
                 changed_meshes.remove(handle);
@@ -588,36 +2361,101 @@ pub fn mesh_resource_provider_system(
 
     for changed_mesh_handle in changed_meshes.iter() {
         if let Some(mesh) = meshes.get(changed_mesh_handle) {
-            let vertex_bytes = mesh
-                .get_vertex_buffer_bytes(&vertex_buffer_descriptor, true)
-                .unwrap();
-            // TODO: use a staging buffer here
-            let vertex_buffer = render_resource_context.create_buffer_with_data(
-                BufferInfo {
-                    buffer_usage: BufferUsage::VERTEX,
-                    ..Default::default()
-                },
+            let index_format = mesh.index_format();
+            // garbage here turns into a driver crash or a mess on screen, much later. An invalid
+            // mesh keeps whatever it uploaded last, if anything
+            if cfg!(debug_assertions) {
+                if let Err(error) = mesh.validate(index_format) {
+                    log::error!(
+                        "Mesh {:?} is invalid and won't be uploaded: {}",
+                        changed_mesh_handle,
+                        error
+                    );
+                    continue;
+                }
+            }
+            state
+                .unpackable
+                .retain(|(mesh, _)| mesh != changed_mesh_handle);
+            let vertex_bytes =
+                match pack_vertex_buffer(*changed_mesh_handle, mesh, vertex_buffer_descriptor) {
+                    Some(vertex_bytes) => vertex_bytes,
+                    None => continue,
+                };
+            let dirty_vertices = mesh.dirty_vertices.take();
+            // meshes without indices only get a vertex buffer and are drawn by vertex count
+            let index_bytes = mesh.get_index_buffer_bytes(index_format);
+            if write_vertex_buffer(
+                render_resource_context,
+                &mut staging_buffers,
+                *changed_mesh_handle,
+                VERTEX_BUFFER_ASSET_INDEX,
+                vertex_buffer_descriptor.stride as usize,
                 &vertex_bytes,
-            );
+                dirty_vertices.clone(),
+            ) {
+                update_index_buffer(
+                    render_resource_context,
+                    &mut state.uploaded_indices,
+                    *changed_mesh_handle,
+                    index_bytes.as_deref(),
+                );
+                for (layout_name, index) in state.custom_layouts.iter() {
+                    if get_buffer(render_resource_context, *changed_mesh_handle, *index).is_none() {
+                        continue;
+                    }
+                    let written = match vertex_buffer_descriptors.get(layout_name) {
+                        Some(descriptor) => {
+                            match pack_vertex_buffer(*changed_mesh_handle, mesh, descriptor) {
+                                Some(vertex_bytes) => write_vertex_buffer(
+                                    render_resource_context,
+                                    &mut staging_buffers,
+                                    *changed_mesh_handle,
+                                    *index,
+                                    descriptor.stride as usize,
+                                    &vertex_bytes,
+                                    dirty_vertices.clone(),
+                                ),
+                                None => {
+                                    state.unpackable.insert((*changed_mesh_handle, *index));
+                                    false
+                                }
+                            }
+                        }
+                        None => false,
+                    };
+                    if !written {
+                        // packed again when it's next needed
+                        remove_mesh_buffer(render_resource_context, *changed_mesh_handle, *index);
+                    }
+                }
+                continue;
+            }
 
-            let index_bytes = mesh.get_index_buffer_bytes(IndexFormat::Uint16).unwrap();
-            let index_buffer = render_resource_context.create_buffer_with_data(
+            // new, or resized
+            remove_current_mesh_resources(
+                render_resource_context,
+                *changed_mesh_handle,
+                &state.custom_layouts,
+            );
+            state.uploaded_indices.remove(changed_mesh_handle);
+            let vertex_buffer = render_resource_context.create_buffer_with_data(
                 BufferInfo {
-                    buffer_usage: BufferUsage::INDEX,
+                    buffer_usage: BufferUsage::VERTEX | BufferUsage::COPY_DST,
                     ..Default::default()
                 },
-                &index_bytes,
+                &vertex_bytes,
             );
-
             render_resource_context.set_asset_resource(
                 *changed_mesh_handle,
                 RenderResourceId::Buffer(vertex_buffer),
                 VERTEX_BUFFER_ASSET_INDEX,
             );
-            render_resource_context.set_asset_resource(
+            update_index_buffer(
+                render_resource_context,
+                &mut state.uploaded_indices,
                 *changed_mesh_handle,
-                RenderResourceId::Buffer(index_buffer),
-                INDEX_BUFFER_ASSET_INDEX,
+                index_bytes.as_deref(),
             );
         }
     }
@@ -627,29 +2465,257 @@ pub fn mesh_resource_provider_system(
         if let Some(mesh) = meshes.get(&handle) {
             for render_pipeline in render_pipelines.pipelines.iter_mut() {
                 render_pipeline.specialization.primitive_topology = mesh.primitive_topology;
+                render_pipeline.specialization.index_format = mesh.index_format();
             }
         }
 
-        if let Some(RenderResourceId::Buffer(vertex_buffer)) =
-            render_resource_context.get_asset_resource(*handle, VERTEX_BUFFER_ASSET_INDEX)
-        {
-            render_pipelines.bindings.set_vertex_buffer(
-                "Vertex",
-                vertex_buffer,
-                render_resource_context
-                    .get_asset_resource(*handle, INDEX_BUFFER_ASSET_INDEX)
-                    .and_then(|r| {
-                        if let RenderResourceId::Buffer(buffer) = r {
-                            Some(buffer)
-                        } else {
-                            None
+        let index_buffer = render_resource_context
+            .get_asset_resource(*handle, INDEX_BUFFER_ASSET_INDEX)
+            .and_then(|r| {
+                if let RenderResourceId::Buffer(buffer) = r {
+                    Some(buffer)
+                } else {
+                    None
+                }
+            });
+        match render_resource_context.get_asset_resource(*handle, VERTEX_BUFFER_ASSET_INDEX) {
+            Some(RenderResourceId::Buffer(vertex_buffer)) => render_pipelines
+                .bindings
+                .set_vertex_buffer("Vertex", vertex_buffer, index_buffer),
+            // not uploaded yet or never valid, so there's nothing to pack other layouts from
+            _ => continue,
+        }
+
+        // layouts the pipelines use besides `Vertex`. They're only known once a pipeline has
+        // been compiled, so each one gets packed the first time it's needed for this mesh.
+        // Per-instance buffers don't come from the mesh, whoever spawns the instances sets them
+        let mut custom_layouts = Vec::new();
+        for render_pipeline in render_pipelines.pipelines.iter() {
+            let layout = pipeline_compiler
+                .get_specialized_pipeline(render_pipeline.pipeline, &render_pipeline.specialization)
+                .and_then(|specialized| pipelines.get(&specialized))
+                .and_then(|descriptor| descriptor.layout.as_ref());
+            if let Some(layout) = layout {
+                for descriptor in layout.vertex_buffer_descriptors.iter() {
+                    if descriptor.name != "Vertex"
+                        && descriptor.step_mode == InputStepMode::Vertex
+                        && !custom_layouts.contains(&descriptor.name)
+                    {
+                        custom_layouts.push(descriptor.name.clone());
+                    }
+                }
+            }
+        }
+        for layout_name in custom_layouts {
+            let index = state.custom_layout_index(&layout_name);
+            let vertex_buffer = match render_resource_context.get_asset_resource(*handle, index) {
+                Some(RenderResourceId::Buffer(buffer)) => buffer,
+                _ => {
+                    let (mesh, descriptor) = match (
+                        meshes.get(&handle),
+                        vertex_buffer_descriptors.get(&layout_name),
+                    ) {
+                        (Some(mesh), Some(descriptor)) => (mesh, descriptor),
+                        _ => continue,
+                    };
+                    if state.unpackable.contains(&(*handle, index)) {
+                        continue;
+                    }
+                    let vertex_bytes = match pack_vertex_buffer(*handle, mesh, descriptor) {
+                        Some(vertex_bytes) => vertex_bytes,
+                        None => {
+                            state.unpackable.insert((*handle, index));
+                            continue;
                         }
-                    }),
-            );
+                    };
+                    let vertex_buffer = render_resource_context.create_buffer_with_data(
+                        BufferInfo {
+                            buffer_usage: BufferUsage::VERTEX | BufferUsage::COPY_DST,
+                            ..Default::default()
+                        },
+                        &vertex_bytes,
+                    );
+                    render_resource_context.set_asset_resource(
+                        *handle,
+                        RenderResourceId::Buffer(vertex_buffer),
+                        index,
+                    );
+                    vertex_buffer
+                }
+            };
+            render_pipelines
+                .bindings
+                .set_vertex_buffer(&layout_name, vertex_buffer, index_buffer);
+        }
+    }
+}
+
+/// Finishes the meshes bevy's `draw_render_pipelines_system` leaves undrawn, the ones without
+/// an index buffer. That system still sets their pipelines, bind groups and vertex buffers, so
+/// this only adds a draw by vertex count after each pipeline's commands. Runs after it in
+/// `stage::DRAW`.
+pub fn draw_non_indexed_system(
+    render_resource_context: Res<Box<dyn RenderResourceContext>>,
+    pipelines: Res<Assets<PipelineDescriptor>>,
+    mut query: Query<(&mut Draw, &RenderPipelines)>,
+) {
+    let render_resource_context = &**render_resource_context;
+    for (mut draw, render_pipelines) in &mut query.iter() {
+        if !draw.is_visible {
+            continue;
+        }
+        match render_pipelines.bindings.get_vertex_buffer("Vertex") {
+            Some((_, None)) => {}
+            // indexed, or not uploaded yet
+            _ => continue,
+        }
+
+        let mut render_commands = Vec::with_capacity(draw.render_commands.len() + 1);
+        let mut vertex_count = 0;
+        for render_command in draw.render_commands.drain(..) {
+            if let RenderCommand::SetPipeline { pipeline } = &render_command {
+                push_non_indexed_draw(&mut render_commands, vertex_count);
+                vertex_count = pipelines
+                    .get(pipeline)
+                    .and_then(|descriptor| descriptor.layout.as_ref())
+                    .map_or(0, |layout| {
+                        non_indexed_vertex_count(render_resource_context, layout, render_pipelines)
+                    });
+            }
+            render_commands.push(render_command);
+        }
+        push_non_indexed_draw(&mut render_commands, vertex_count);
+        draw.render_commands = render_commands;
+    }
+}
+
+fn push_non_indexed_draw(render_commands: &mut Vec<RenderCommand>, vertex_count: usize) {
+    if vertex_count > 0 {
+        render_commands.push(RenderCommand::Draw {
+            vertices: 0..vertex_count as u32,
+            instances: 0..1,
+        });
+    }
+}
+
+/// Every per-vertex buffer in the pipeline's layout holds one element per vertex, so the
+/// smallest of them is what can be drawn. 0 if one is missing.
+fn non_indexed_vertex_count(
+    render_resource_context: &dyn RenderResourceContext,
+    layout: &PipelineLayout,
+    render_pipelines: &RenderPipelines,
+) -> usize {
+    layout
+        .vertex_buffer_descriptors
+        .iter()
+        .filter(|descriptor| descriptor.stride > 0 && descriptor.step_mode == InputStepMode::Vertex)
+        .map(|descriptor| {
+            render_pipelines
+                .bindings
+                .get_vertex_buffer(&descriptor.name)
+                .and_then(|(buffer, _)| render_resource_context.get_buffer_info(buffer))
+                .map_or(0, |info| info.size / descriptor.stride as usize)
+        })
+        .min()
+        .unwrap_or(0)
+}
+
+/// bevy's `draw_render_pipelines_system` counts the indices of an index buffer as 16 bit ones,
+/// so a mesh uploaded with `IndexFormat::Uint32` would be drawn with twice as many indices as
+/// it has. This corrects the range of its indexed draws. Runs after it in `stage::DRAW`.
+pub fn draw_uint32_indices_system(
+    render_resource_context: Res<Box<dyn RenderResourceContext>>,
+    mut query: Query<(&mut Draw, &RenderPipelines)>,
+) {
+    for (mut draw, render_pipelines) in &mut query.iter() {
+        if !draw.is_visible {
+            continue;
+        }
+        let uint32 = render_pipelines
+            .pipelines
+            .iter()
+            .any(|pipeline| pipeline.specialization.index_format == IndexFormat::Uint32);
+        if !uint32 {
+            continue;
+        }
+        let index_count = match render_pipelines
+            .bindings
+            .get_vertex_buffer("Vertex")
+            .and_then(|(_, index_buffer)| index_buffer)
+            .and_then(|index_buffer| render_resource_context.get_buffer_info(index_buffer))
+        {
+            Some(info) => (info.size / std::mem::size_of::<u32>()) as u32,
+            None => continue,
+        };
+
+        for render_command in draw.render_commands.iter_mut() {
+            if let RenderCommand::DrawIndexed { indices, .. } = render_command {
+                *indices = 0..index_count;
+            }
+        }
+    }
+}
+
+/// Draws every instance of meshes whose pipelines read a per-instance buffer, one instance per
+/// element of it. bevy's `draw_render_pipelines_system` and `draw_non_indexed_system` only ever
+/// draw instance 0. Runs after both in `stage::DRAW`.
+pub fn draw_instanced_system(
+    render_resource_context: Res<Box<dyn RenderResourceContext>>,
+    pipelines: Res<Assets<PipelineDescriptor>>,
+    mut query: Query<(&mut Draw, &RenderPipelines)>,
+) {
+    let render_resource_context = &**render_resource_context;
+    for (mut draw, render_pipelines) in &mut query.iter() {
+        if !draw.is_visible {
+            continue;
+        }
+
+        let mut count = None;
+        for render_command in draw.render_commands.iter_mut() {
+            match render_command {
+                RenderCommand::SetPipeline { pipeline } => {
+                    count = pipelines
+                        .get(pipeline)
+                        .and_then(|descriptor| descriptor.layout.as_ref())
+                        .and_then(|layout| {
+                            instance_count(render_resource_context, layout, render_pipelines)
+                        });
+                }
+                RenderCommand::Draw { instances, .. }
+                | RenderCommand::DrawIndexed { instances, .. } => {
+                    if let Some(count) = count {
+                        *instances = 0..count as u32;
+                    }
+                }
+                _ => {}
+            }
         }
     }
 }
 
+/// Like `non_indexed_vertex_count` over the per-instance buffers. `None` if the layout has none,
+/// 0 if one is missing.
+fn instance_count(
+    render_resource_context: &dyn RenderResourceContext,
+    layout: &PipelineLayout,
+    render_pipelines: &RenderPipelines,
+) -> Option<usize> {
+    layout
+        .vertex_buffer_descriptors
+        .iter()
+        .filter(|descriptor| {
+            descriptor.stride > 0 && descriptor.step_mode == InputStepMode::Instance
+        })
+        .map(|descriptor| {
+            render_pipelines
+                .bindings
+                .get_vertex_buffer(&descriptor.name)
+                .and_then(|(buffer, _)| render_resource_context.get_buffer_info(buffer))
+                .map_or(0, |info| info.size / descriptor.stride as usize)
+        })
+        .min()
+}
+
 #[cfg(test)]
 mod tests {
     use super::{AsVertexBufferDescriptor, Mesh, VertexAttribute};
@@ -681,6 +2747,8 @@ mod tests {
                 VertexAttribute::uv(uvs),
             ],
             indices: None,
+            dirty_vertices: Default::default(),
+            bounds: Default::default(),
         };
 
         let expected_vertices = &[
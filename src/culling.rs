@@ -0,0 +1,130 @@
+use crate::wasd_camera::CameraMarker;
+use bevy::{
+    app::{EventReader, Events},
+    asset::AssetEvent,
+    prelude::*,
+    render::{
+        camera::Camera,
+        draw::Draw,
+        mesh::{Aabb, BoundingSphere, Bounds},
+    },
+};
+
+/// The stage `frustum_culling` runs in, after POST_UPDATE so transforms and mesh bounds are
+/// up to date.
+pub const CULLING: &str = "culling";
+
+/// Marks entities `frustum_culling` hid, it only ever shows those again.
+pub struct Culled;
+
+/// Culls an instanced entity by the bounds of all its instances, in the entity's space, instead
+/// of by its mesh.
+pub struct InstanceBounds(pub Bounds);
+
+/// The six planes of a camera's view volume, each facing inward as `(normal, distance)`.
+#[derive(Clone, Copy, Debug)]
+pub struct Frustum {
+    planes: [Vec4; 6],
+}
+
+impl Frustum {
+    /// Gribb-Hartmann: the planes come straight out of the rows of the matrix. Clip space
+    /// depth runs from 0 to 1 like wgpu expects.
+    pub fn from_view_projection(view_projection: &Mat4) -> Self {
+        let rows = view_projection.transpose();
+        let (x, y, z, w) = (rows.x_axis(), rows.y_axis(), rows.z_axis(), rows.w_axis());
+        let mut planes = [w + x, w - x, w + y, w - y, z, w - z];
+        for plane in planes.iter_mut() {
+            *plane /= plane.truncate().length();
+        }
+        Self { planes }
+    }
+
+    fn distance(plane: &Vec4, point: Vec3) -> f32 {
+        plane.truncate().dot(point) + plane.w()
+    }
+
+    /// `false` only if the sphere is entirely outside, spheres near a corner can pass
+    /// without being visible.
+    pub fn intersects_sphere(&self, sphere: &BoundingSphere) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| Self::distance(plane, sphere.center) >= -sphere.radius)
+    }
+
+    /// Checks the corner of `aabb` furthest along each plane's normal.
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        self.planes.iter().all(|plane| {
+            let pick = |n: f32, min: f32, max: f32| if n >= 0.0 { max } else { min };
+            let corner = Vec3::new(
+                pick(plane.x(), aabb.min.x(), aabb.max.x()),
+                pick(plane.y(), aabb.min.y(), aabb.max.y()),
+                pick(plane.z(), aabb.min.z(), aabb.max.z()),
+            );
+            Self::distance(plane, corner) >= 0.0
+        })
+    }
+}
+
+// computes the bounds of new and changed meshes up front, instead of when culling first needs them
+pub fn mesh_bounds_system(
+    mut event_reader: Local<EventReader<AssetEvent<Mesh>>>,
+    mesh_events: Res<Events<AssetEvent<Mesh>>>,
+    meshes: Res<Assets<Mesh>>,
+) {
+    for event in event_reader.iter(&mesh_events) {
+        match event {
+            AssetEvent::Created { handle } | AssetEvent::Modified { handle } => {
+                if let Some(mesh) = meshes.get(handle) {
+                    mesh.bounds();
+                }
+            }
+            AssetEvent::Removed { .. } => {}
+        }
+    }
+}
+
+// hides meshes outside the view of the camera, the sphere rules out most of them and the box
+// catches long thin ones like rings. Meshes without bounds are always drawn, and meshes hidden
+// by something else are left alone. `InstanceBounds` take the place of the mesh's
+pub fn frustum_culling(
+    mut commands: Commands,
+    meshes: Res<Assets<Mesh>>,
+    mut camera_query: Query<(&Camera, &Transform, &CameraMarker)>,
+    mut query: Query<(
+        Entity,
+        &Handle<Mesh>,
+        &Transform,
+        &mut Draw,
+        Option<&Culled>,
+        Option<&InstanceBounds>,
+    )>,
+) {
+    for (camera, camera_transform, _cam) in &mut camera_query.iter() {
+        let frustum = Frustum::from_view_projection(
+            &(camera.projection_matrix * camera_transform.value.inverse()),
+        );
+        for (entity, handle, transform, mut draw, culled, instance_bounds) in &mut query.iter() {
+            let bounds = match instance_bounds {
+                Some(instance_bounds) => Some(instance_bounds.0),
+                None => meshes.get(&handle).and_then(|mesh| mesh.bounds()),
+            };
+            let in_view = match bounds {
+                Some(bounds) => {
+                    frustum.intersects_sphere(&bounds.sphere.transformed(&transform.value))
+                        && frustum.intersects_aabb(&bounds.aabb.transformed(&transform.value))
+                }
+                None => true,
+            };
+            if culled.is_some() {
+                if in_view {
+                    draw.is_visible = true;
+                    commands.remove_one::<Culled>(entity);
+                }
+            } else if !in_view && draw.is_visible {
+                draw.is_visible = false;
+                commands.insert_one(entity, Culled);
+            }
+        }
+    }
+}
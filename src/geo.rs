@@ -0,0 +1,178 @@
+use bevy::{
+    math::{vec3, Mat3},
+    prelude::*,
+};
+
+// Everything here is in planet space, centered on the origin with +Y as north, and angles
+// are in radians.
+
+/// Longitude 0 is +X and increases eastward, counter-clockwise seen from above the north pole.
+pub fn lat_long_to_direction(latitude: f32, longitude: f32) -> Vec3 {
+    vec3(
+        latitude.cos() * longitude.cos(),
+        latitude.sin(),
+        -latitude.cos() * longitude.sin(),
+    )
+}
+
+/// Doesn't need a unit vector, any point relative to the planet center works.
+pub fn direction_to_lat_long(direction: Vec3) -> (f32, f32) {
+    let direction = direction.normalize();
+    let latitude = direction.y().max(-1.0).min(1.0).asin();
+    let longitude = (-direction.z()).atan2(direction.x());
+    (latitude, longitude)
+}
+
+/// `radius` is the distance from the planet center, not the height above the ground.
+pub fn lat_long_to_point(latitude: f32, longitude: f32, radius: f32) -> Vec3 {
+    lat_long_to_direction(latitude, longitude) * radius
+}
+
+/// Latitude, longitude and distance from the planet center.
+pub fn point_to_lat_long(point: Vec3) -> (f32, f32, f32) {
+    let (latitude, longitude) = direction_to_lat_long(point);
+    (latitude, longitude, point.length())
+}
+
+/// Angle between two points as seen from the planet center. Stays accurate for nearby
+/// points, where `acos` of the dot product loses precision.
+pub fn central_angle(from: Vec3, to: Vec3) -> f32 {
+    from.cross(to).length().atan2(from.dot(to))
+}
+
+/// Distance along the surface of a sphere of `radius` between the directions of two points.
+pub fn great_circle_distance(from: Vec3, to: Vec3, radius: f32) -> f32 {
+    central_angle(from, to) * radius
+}
+
+/// Compass heading to set off from `from` towards `to`, clockwise from north in
+/// `0..2π`. Undefined at the poles, where every direction is south or north.
+pub fn initial_bearing(from: Vec3, to: Vec3) -> f32 {
+    let frame = LocalFrame::at(from);
+    let to = to.normalize();
+    let bearing = to.dot(frame.east).atan2(to.dot(frame.north));
+    if bearing < 0.0 {
+        bearing + 2.0 * std::f32::consts::PI
+    } else {
+        bearing
+    }
+}
+
+/// Moves along the great circle between the directions of two points, `t` of 0.0 is
+/// `from` and 1.0 is `to`. Returns a unit vector.
+pub fn slerp(from: Vec3, to: Vec3, t: f32) -> Vec3 {
+    let (from, to) = (from.normalize(), to.normalize());
+    let angle = central_angle(from, to);
+    if angle < 1e-6 {
+        return from;
+    }
+    ((from * ((1.0 - t) * angle).sin() + to * (t * angle).sin()) / angle.sin()).normalize()
+}
+
+/// East/north/up axes on the surface. `east x north = up`.
+#[derive(Clone, Copy, Debug)]
+pub struct LocalFrame {
+    pub east: Vec3,
+    pub north: Vec3,
+    pub up: Vec3,
+}
+
+impl LocalFrame {
+    /// At the poles east is picked as if coming from longitude 0.
+    pub fn at(direction: Vec3) -> Self {
+        let up = direction.normalize();
+        let east = if up.y().abs() < 0.9999 {
+            Vec3::unit_y().cross(up).normalize()
+        } else {
+            -Vec3::unit_z()
+        };
+        Self {
+            east,
+            north: up.cross(east),
+            up,
+        }
+    }
+
+    /// Rotates a model so its +X points east, +Y up and +Z south, the way bevy models
+    /// standing on flat ground expect.
+    pub fn rotation(&self) -> Quat {
+        Quat::from_rotation_mat3(&Mat3::from_cols(self.east, self.up, -self.north))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::{FRAC_PI_2, PI};
+
+    #[test]
+    fn test_lat_long_round_trip() {
+        for lat in (-8..=8).map(|i| (i * 10) as f32) {
+            for long in (-17..=17).map(|i| (i * 10) as f32) {
+                let point = lat_long_to_point(lat.to_radians(), long.to_radians(), 3.0);
+                let (latitude, longitude, radius) = point_to_lat_long(point);
+                assert!(
+                    (latitude.to_degrees() - lat).abs() < 1e-3,
+                    "{} {}",
+                    lat,
+                    long
+                );
+                assert!(
+                    (longitude.to_degrees() - long).abs() < 1e-3,
+                    "{} {}",
+                    lat,
+                    long
+                );
+                assert!((radius - 3.0).abs() < 1e-5);
+            }
+        }
+    }
+
+    #[test]
+    fn test_great_circle_distance() {
+        // a quarter of the way around is a quarter of the circumference
+        let equator = lat_long_to_direction(0.0, 0.0);
+        let pole = lat_long_to_direction(FRAC_PI_2, 0.0);
+        let east = lat_long_to_direction(0.0, FRAC_PI_2);
+        assert!((great_circle_distance(equator, pole, 2.0) - PI).abs() < 1e-5);
+        assert!((great_circle_distance(equator, east, 2.0) - PI).abs() < 1e-5);
+        assert!(great_circle_distance(equator, equator * 5.0, 2.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_initial_bearing() {
+        let from = lat_long_to_direction(0.0, 0.0);
+        let towards = |lat: f32, long: f32| {
+            initial_bearing(
+                from,
+                lat_long_to_direction(lat.to_radians(), long.to_radians()),
+            )
+        };
+        assert!(towards(10.0, 0.0).abs() < 1e-5, "north");
+        assert!((towards(0.0, 10.0) - FRAC_PI_2).abs() < 1e-5, "east");
+        assert!((towards(-10.0, 0.0) - PI).abs() < 1e-5, "south");
+        assert!((towards(0.0, -10.0) - 3.0 * FRAC_PI_2).abs() < 1e-5, "west");
+    }
+
+    #[test]
+    fn test_local_frame() {
+        let directions = [
+            vec3(1.0, 0.0, 0.0),
+            vec3(0.3, -0.5, 0.8),
+            vec3(-2.0, 1.0, 0.5),
+            vec3(0.0, 1.0, 0.0),
+            vec3(0.0, -1.0, 0.0),
+        ];
+        for direction in directions.iter() {
+            let frame = LocalFrame::at(*direction);
+            for axis in [frame.east, frame.north, frame.up].iter() {
+                assert!((axis.length() - 1.0).abs() < 1e-5);
+            }
+            assert!(frame.east.dot(frame.north).abs() < 1e-5);
+            assert!(frame.east.dot(frame.up).abs() < 1e-5);
+            assert!(frame.north.dot(frame.up).abs() < 1e-5);
+            assert!((frame.east.cross(frame.north) - frame.up).length() < 1e-5);
+            assert!((frame.up - direction.normalize()).length() < 1e-5);
+        }
+    }
+}
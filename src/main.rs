@@ -3,15 +3,45 @@ use bevy::{
     math::{vec2, vec3},
     prelude::*,
     render::{
-        mesh::shape,
+        mesh::{
+            draw_instanced_system, draw_non_indexed_system, draw_uint32_indices_system, shape,
+            MeshStagingBuffers, MeshStagingNode, VertexAttribute,
+        },
         pipeline::{DynamicBinding, PipelineDescriptor, PipelineSpecialization, RenderPipeline},
         render_graph::{base, AssetRenderResourcesNode, RenderGraph},
         renderer::RenderResources,
         shader::{asset_shader_defs_system, ShaderDefs, ShaderStage, ShaderStages},
     },
 };
-use noise::*;
+mod asteroid;
+mod clouds;
+mod culling;
+mod geo;
+#[cfg(test)]
+mod mesh_tests;
+mod navigation;
+mod ocean;
+mod planet;
+mod raycast;
+mod rings;
+mod rng;
+mod scatter;
+mod shaders;
+mod surface;
+mod tiles;
 mod wasd_camera;
+use asteroid::{AsteroidConfig, AsteroidGenerator};
+use clouds::CloudMaterial;
+use navigation::NavGraph;
+use ocean::OceanMaterial;
+use planet::{Planet, PlanetConfig};
+use raycast::PlanetPick;
+use rings::{RingConfig, RingMaterial};
+use rng::Rng;
+use scatter::{Scatter, ScatterRenderer, ScatterRule};
+use shaders::glsl;
+use surface::{Biome, PlanetSurface};
+use tiles::HexGrid;
 use wasd_camera::{CameraConfig, CameraMarker};
 
 #[derive(RenderResources, ShaderDefs)]
@@ -21,6 +51,26 @@ struct StellarMaterial {
     pub texture: Option<Handle<Texture>>,
     pub atmo_radius: f32,
     pub camera_pos: Mat4,
+    /// detail normal map, needs the mesh to have tangents
+    #[shader_def]
+    pub normal_map: Option<Handle<Texture>>,
+    /// one tile per `Biome`, side by side in a single texture. It stands in for a texture
+    /// array, which bevy's `Texture` can't hold, so the shader insets every tile to keep
+    /// filtering from mixing in the neighbouring biome
+    #[shader_def]
+    pub biome_textures: Option<Handle<Texture>>,
+    pub biome_layers: f32,
+    /// project `biome_textures`, or `texture` without them, along the three axes instead of
+    /// using uvs
+    #[render_resources(ignore)]
+    #[shader_def]
+    pub triplanar: bool,
+    pub texture_scale: f32,
+    /// six faces side by side (+X, -X, +Y, -Y, +Z, -Z), sampled by direction from the center
+    #[shader_def]
+    pub cubemap: Option<Handle<Texture>>,
+    /// lights the atmosphere and the detail normal map
+    pub sun_position: Vec4,
 }
 // #[derive(RenderResources, ShaderDefs)]
 // struct QuadMaterial {
@@ -42,22 +92,54 @@ impl AssetHandles {
 }
 
 fn main() {
+    let mut config = PlanetConfig::default();
+    config.solve_sea_level();
     App::build()
         .add_resource(Msaa { samples: 4 })
         .add_default_plugins()
         .add_asset::<StellarMaterial>()
+        .add_asset::<RingMaterial>()
+        .add_asset::<CloudMaterial>()
+        .add_asset::<OceanMaterial>()
         .add_plugin(FrameTimeDiagnosticsPlugin::default())
         .add_plugin(PrintDiagnosticsPlugin::default())
         .add_resource(CameraConfig::default())
+        // lets gameplay ask about the ground without touching the mesh
+        .add_resource(PlanetSurface::new(&config))
+        .add_resource(config)
         .add_resource(AssetHandles::default())
+        .add_resource(PlanetPick::default())
+        // partial mesh updates, written by the mesh resource provider for the staging node
+        .add_resource(MeshStagingBuffers::default())
         .add_plugin(wasd_camera::WasdCamera)
+        .add_startup_system(planet::register_planet_vertex_layout.system())
+        .add_startup_system(ocean::register_ocean_vertex_layout.system())
+        .add_startup_system(scatter::register_scatter_layouts.system())
         .add_startup_system(setup.system())
         .add_system(update_camera_pass_through.system())
         .add_system(move_quad_with_camera.system())
+        .add_system(clouds::update_clouds.system())
+        .add_system(ocean::update_ocean.system())
+        .add_system(raycast::pick_planet.system())
+        .add_system(scatter::stream_scatter::<StellarMaterial>.system())
         .add_system_to_stage(
             stage::POST_UPDATE,
             asset_shader_defs_system::<StellarMaterial>.system(),
         )
+        .add_system_to_stage(stage::POST_UPDATE, culling::mesh_bounds_system.system())
+        // after the transforms and mesh bounds are updated
+        .add_stage_after(stage::POST_UPDATE, culling::CULLING)
+        // hides what's out of view before bevy's draw system records commands for it
+        .add_system_to_stage(culling::CULLING, culling::frustum_culling.system())
+        // bevy's draw system leaves out meshes without indices, like flat shaded ones
+        .add_system_to_stage(bevy::render::stage::DRAW, draw_non_indexed_system.system())
+        // bevy's draw system counts every index buffer as 16 bit
+        .add_system_to_stage(
+            bevy::render::stage::DRAW,
+            draw_uint32_indices_system.system(),
+        )
+        // bevy's draw system only ever draws the first instance
+        .add_system_to_stage(bevy::render::stage::DRAW, draw_instanced_system.system())
         .run();
 }
 
@@ -65,23 +147,38 @@ fn setup(
     mut commands: Commands,
     // asset_server: Res<AssetServer>,
     mut asset_handles: ResMut<AssetHandles>,
+    config: Res<PlanetConfig>,
+    surface: Res<PlanetSurface>,
     mut pipelines: ResMut<Assets<PipelineDescriptor>>,
     mut shaders: ResMut<Assets<Shader>>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StellarMaterial>>,
+    mut ring_materials: ResMut<Assets<RingMaterial>>,
+    mut cloud_materials: ResMut<Assets<CloudMaterial>>,
+    mut ocean_materials: ResMut<Assets<OceanMaterial>>,
+    mut textures: ResMut<Assets<Texture>>,
     mut render_graph: ResMut<RenderGraph>,
 ) {
     // let texture_handle = asset_server.load("assets/unscaledFinalPlanet.png").unwrap();
 
+    let fragment_shader = shaders.add(Shader::from_glsl(
+        ShaderStage::Fragment,
+        &glsl(include_str!("../assets/frag_shader.frag")),
+    ));
     let pipeline_handle = pipelines.add(PipelineDescriptor::default_config(ShaderStages {
         vertex: shaders.add(Shader::from_glsl(
             ShaderStage::Vertex,
             include_str!("../assets/vert_shader.vert"),
         )),
-        fragment: Some(shaders.add(Shader::from_glsl(
-            ShaderStage::Fragment,
-            include_str!("../assets/frag_shader.frag"),
-        ))),
+        fragment: Some(fragment_shader),
+    }));
+    // the same material on scatter templates, placed by their instance buffer
+    let scatter_pipeline_handle = pipelines.add(PipelineDescriptor::default_config(ShaderStages {
+        vertex: shaders.add(Shader::from_glsl(
+            ShaderStage::Vertex,
+            include_str!("../assets/scatter_shader.vert"),
+        )),
+        fragment: Some(fragment_shader),
     }));
     render_graph.add_system_node(
         "stellar_material",
@@ -90,115 +187,264 @@ fn setup(
     render_graph
         .add_node_edge("stellar_material", base::node::MAIN_PASS)
         .unwrap();
+    render_graph.add_node("mesh_staging", MeshStagingNode);
+    render_graph
+        .add_node_edge("mesh_staging", base::node::MAIN_PASS)
+        .unwrap();
+    let specialization = PipelineSpecialization {
+        dynamic_bindings: vec![
+            // Transform
+            DynamicBinding {
+                bind_group: 1,
+                binding: 0,
+            },
+            // StellarMaterial_basecolor
+            DynamicBinding {
+                bind_group: 1,
+                binding: 1,
+            },
+            // StellarMaterial_texture
+            DynamicBinding {
+                bind_group: 1,
+                binding: 2,
+            },
+            // StellarMaterial_atmo_radius
+            DynamicBinding {
+                bind_group: 1,
+                binding: 4,
+            },
+            // StellarMaterial_camera_pos
+            DynamicBinding {
+                bind_group: 1,
+                binding: 5,
+            },
+            // StellarMaterial_biome_layers
+            DynamicBinding {
+                bind_group: 1,
+                binding: 10,
+            },
+            // StellarMaterial_texture_scale
+            DynamicBinding {
+                bind_group: 1,
+                binding: 11,
+            },
+            // StellarMaterial_sun_position
+            DynamicBinding {
+                bind_group: 1,
+                binding: 14,
+            },
+        ],
+        ..Default::default()
+    };
     let specialized_pipeline = RenderPipelines::from_pipelines(vec![RenderPipeline::specialized(
         pipeline_handle,
-        PipelineSpecialization {
-            dynamic_bindings: vec![
-                // Transform
-                DynamicBinding {
-                    bind_group: 1,
-                    binding: 0,
-                },
-                // StellarMaterial_basecolor
-                DynamicBinding {
-                    bind_group: 1,
-                    binding: 1,
-                },
-                // StellarMaterial_texture
-                DynamicBinding {
-                    bind_group: 1,
-                    binding: 2,
-                },
-                // StellarMaterial_atmo_radius
-                DynamicBinding {
-                    bind_group: 1,
-                    binding: 4,
-                },
-                // StellarMaterial_camera_pos
-                DynamicBinding {
-                    bind_group: 1,
-                    binding: 5,
-                },
-            ],
-            ..Default::default()
-        },
+        specialization.clone(),
     )]);
 
+    let sun_position = Vec4::new(40000.0, -4.0, 100000.0, 1.0);
     let material = materials.add(StellarMaterial {
         basecolor: Color::rgb(1.0, 1.0, 1.0),
         texture: None,
-        atmo_radius: 45000.0,
+        atmo_radius: config.atmo_radius,
         camera_pos: Mat4::from_translation(vec3(0.0, 0.0, 100000.)),
+        normal_map: None,
+        biome_textures: None,
+        biome_layers: 2.0,
+        triplanar: false,
+        texture_scale: 1.0 / 5000.0,
+        cubemap: None,
+        sun_position,
         // texture: Some(texture_handle),
     });
     commands.spawn(LightComponents {
-        translation: Translation::new(40000.0, -4.0, 100000.0),
+        translation: Translation::new(sun_position.x(), sun_position.y(), sun_position.z()),
         ..Default::default()
     });
-    let mut noise = Vec::new();
-    let mut mesh = Mesh::from(shape::Icosphere {
-        radius: 50000.,
-        subdivisions: 20,
-    });
-    let mut distance = Vec::new();
-    match mesh.attributes[0].values {
-        bevy::render::mesh::VertexAttributeValues::Float3(ref mut val) => {
-            for verts in val {
-                let x = noise::RidgedMulti::new();
-                let mut n = x.get([verts[0] as f64, verts[1] as f64, verts[2] as f64]);
-                noise.push(n / 2.0 + 1.0);
-                n = (n + 1.0).max(0.5).min(0.8);
-                // n = 0.8;
-                verts[0] *= n as f32;
-                verts[1] *= n as f32;
-                verts[2] *= n as f32;
-                distance.push((verts[0].powi(2) + verts[1].powi(2) + verts[2].powi(2)).sqrt());
-            }
+    let mut mesh = if config.hex_tiles {
+        let mut grid = HexGrid::new(config.subdivisions);
+        for tile in grid.tiles.iter_mut() {
+            tile.color = Biome::classify(surface.elevation(tile.center)).color();
         }
-        _ => {}
-    }
-    // COLOR RANDOMIZER
-    match mesh.attributes[3].values {
-        bevy::render::mesh::VertexAttributeValues::Float4(ref mut val) => {
-            for (i, verts) in val.iter_mut().enumerate() {
-                // let n = noise[i];
-                // let m = if i == 0 {
-                //     noise[noise.len() - 1]
-                // } else {
-                //     noise[i - 1]
-                // };
-                // let o = if i + 1 == noise.len() {
-                //     noise[0]
-                // } else {
-                //     noise[i + 1]
-                // };
-                // // chose 1-n to make the planet colors darker because apparently my values are very close to 1
-                // verts[0] = 1.0 - m as f32;
-                // verts[1] = 1.0 - n as f32;
-                // verts[2] = 1.0 - o as f32;
-                if distance[i] > 35000.0 {
-                    verts[0] = 0.0;
-                    verts[1] = 0.5;
-                    verts[2] = 0.0;
-                } else {
-                    verts[0] = 0.0;
-                    verts[1] = 0.0;
-                    verts[2] = 0.5;
-                }
-            }
+        let mesh = grid.mesh(|center| surface.height(center));
+        commands.insert_resource(grid);
+        mesh
+    } else {
+        let terrain = config.terrain();
+        let mut mesh = Mesh::from(shape::Icosphere {
+            radius: config.radius,
+            subdivisions: config.subdivisions,
+            split_seam: false,
+        });
+        let mut distance = Vec::new();
+        for verts in mesh
+            .attribute_mut::<[f32; 3]>(VertexAttribute::POSITION)
+            .unwrap()
+        {
+            let dir = Vec3::from(*verts).normalize();
+            let height = terrain.height(dir);
+            *verts = (dir * height).into();
+            distance.push(height);
         }
-        _ => {}
+        // COLOR RANDOMIZER
+        for (i, verts) in mesh
+            .attribute_mut::<[f32; 4]>(VertexAttribute::COLOR)
+            .unwrap()
+            .iter_mut()
+            .enumerate()
+        {
+            // let n = noise[i];
+            // let m = if i == 0 {
+            //     noise[noise.len() - 1]
+            // } else {
+            //     noise[i - 1]
+            // };
+            // let o = if i + 1 == noise.len() {
+            //     noise[0]
+            // } else {
+            //     noise[i + 1]
+            // };
+            // // chose 1-n to make the planet colors darker because apparently my values are very close to 1
+            // verts[0] = 1.0 - m as f32;
+            // verts[1] = 1.0 - n as f32;
+            // verts[2] = 1.0 - o as f32;
+            let color = Biome::classify(distance[i] - config.sea_level_radius()).color();
+            verts[0] = color.r;
+            verts[1] = color.g;
+            verts[2] = color.b;
+        }
+        mesh
+    };
+    if config.flat_shaded {
+        mesh.duplicate_vertices();
+        mesh.compute_flat_normals().unwrap();
+        mesh.compute_face_colors().unwrap();
+    }
+    mesh.generate_tangents().unwrap();
+    planet::add_planet_attributes(&mut mesh, config.sea_level_radius());
+    if let Some(graph) = NavGraph::from_mesh(&mesh) {
+        commands.insert_resource(graph);
     }
 
     let cube_handle = meshes.add(mesh);
+
+    // RINGS
+    let ring_config = RingConfig::default();
+    let ring_pipelines = rings::ring_pipelines(&mut pipelines, &mut shaders, &mut render_graph);
+    let ring_material = ring_materials.add(RingMaterial {
+        texture: textures.add(rings::ring_texture(&ring_config)),
+        planet_radius: config.radius * config.elevation.1,
+        sun_position,
+    });
+    let ring_mesh = meshes.add(Mesh::from(shape::Annulus {
+        inner_radius: ring_config.inner_radius,
+        outer_radius: ring_config.outer_radius,
+        segments: ring_config.segments,
+    }));
+
+    // OCEAN
+    let ocean_pipelines = ocean::ocean_pipelines(&mut pipelines, &mut shaders, &mut render_graph);
+    let ocean_material = ocean_materials.add(OceanMaterial::new(&config, sun_position));
+    let ocean_mesh = meshes.add(ocean::ocean_mesh(&config));
+
+    // CLOUDS
+    let cloud_pipelines = clouds::cloud_pipelines(&mut pipelines, &mut shaders, &mut render_graph);
+    let cloud_material = cloud_materials.add(CloudMaterial::new(&config, sun_position));
+    let cloud_mesh = meshes.add(Mesh::from(shape::Icosphere {
+        radius: config.cloud_radius(),
+        subdivisions: config.clouds.subdivisions,
+        split_seam: false,
+    }));
+
     commands
         .spawn(MeshComponents {
             mesh: cube_handle,
             render_pipelines: specialized_pipeline.clone(),
             translation: Translation::new(0.0, 0.0, 0.0),
+            // axial tilt, the ocean, rings and clouds are children so they follow it
+            rotation: Rotation(Quat::from_rotation_z(0.4)),
             ..Default::default()
         })
-        .with(material);
+        .with(material)
+        .with(Planet)
+        .with_children(|parent| {
+            parent
+                .spawn(MeshComponents {
+                    mesh: ocean_mesh,
+                    render_pipelines: ocean_pipelines,
+                    draw: Draw {
+                        is_transparent: true,
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                })
+                .with(ocean_material);
+            parent
+                .spawn(MeshComponents {
+                    mesh: ring_mesh,
+                    render_pipelines: ring_pipelines,
+                    draw: Draw {
+                        is_transparent: true,
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                })
+                .with(ring_material);
+            parent
+                .spawn(MeshComponents {
+                    mesh: cloud_mesh,
+                    render_pipelines: cloud_pipelines,
+                    draw: Draw {
+                        is_transparent: true,
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                })
+                .with(cloud_material);
+        });
+
+    // SURFACE OBJECTS
+    // placed once up front, cells near the camera get spawned by stream_scatter
+    commands.insert_resource(Scatter::new(&config, &surface, ScatterRule::defaults()));
+    commands.insert_resource(ScatterRenderer {
+        render_pipelines: RenderPipelines::from_pipelines(vec![RenderPipeline::specialized(
+            scatter_pipeline_handle,
+            specialization,
+        )]),
+        material,
+    });
+
+    // ASTEROID BELT
+    // a handful of variants shared between a few hundred rocks
+    let asteroids = AsteroidGenerator::new(7, AsteroidConfig::default());
+    let asteroid_handles = asteroids
+        .generate_many(8)
+        .into_iter()
+        .map(|mesh| meshes.add(mesh))
+        .collect::<Vec<_>>();
+    let mut rng = Rng::new(7);
+    for i in 0..300 {
+        let angle = rng.range(0.0, std::f32::consts::PI * 2.0);
+        let dist = rng.range(70000.0, 85000.0);
+        commands
+            .spawn(MeshComponents {
+                mesh: asteroid_handles[i % asteroid_handles.len()],
+                render_pipelines: specialized_pipeline.clone(),
+                translation: Translation::new(
+                    angle.cos() * dist,
+                    rng.range(-1500.0, 1500.0),
+                    angle.sin() * dist,
+                ),
+                rotation: Rotation(Quat::from_rotation_ypr(
+                    rng.range(0.0, 6.28),
+                    rng.range(0.0, 6.28),
+                    rng.range(0.0, 6.28),
+                )),
+                scale: Scale(rng.range(0.3, 1.5)),
+                ..Default::default()
+            })
+            .with(material);
+    }
+
     let quad = Mesh::from(shape::Quad {
         size: vec2(100000.0, 100000.0),
         flip: false,
@@ -215,6 +461,13 @@ fn setup(
         // texture: Some(texture_handle),
         atmo_radius: 0.0,
         camera_pos: Transform::default().value,
+        normal_map: None,
+        biome_textures: None,
+        biome_layers: 0.0,
+        triplanar: false,
+        texture_scale: 0.0,
+        cubemap: None,
+        sun_position,
     });
     let quad_handle = meshes.add(quad);
     asset_handles.add_quad(quad_handle);
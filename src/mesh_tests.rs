@@ -0,0 +1,687 @@
+// Tests for the additions to src/color_mesh.rs. That file is built as bevy_render's mesh.rs in
+// the bevy fork rather than as part of this crate, so these go through `bevy::render::mesh` from
+// here. Its own `mod tests` only runs in the fork.
+
+use bevy::{
+    core::AsBytes,
+    render::{
+        mesh::{shape, Mesh, VertexAttribute},
+        pipeline::PrimitiveTopology,
+    },
+};
+
+#[test]
+fn test_annulus() {
+    let mesh = Mesh::from(shape::Annulus {
+        inner_radius: 1.0,
+        outer_radius: 2.0,
+        segments: 16,
+    });
+    assert_eq!(mesh.vertex_count(), 34);
+    let positions = mesh
+        .attribute::<[f32; 3]>(VertexAttribute::POSITION)
+        .unwrap();
+    // inner and outer ring alternate
+    for (i, position) in positions.iter().enumerate() {
+        let radius = (position[0] * position[0] + position[2] * position[2]).sqrt();
+        let expected = if i % 2 == 0 { 1.0 } else { 2.0 };
+        assert!((radius - expected).abs() < 1e-5);
+        assert_eq!(position[1], 0.0);
+    }
+    assert_eq!(mesh.indices.unwrap().len(), 16 * 6);
+}
+
+#[test]
+fn test_round_shapes() {
+    use bevy::math::Vec3;
+    use bevy::render::pipeline::IndexFormat;
+
+    let shapes = vec![
+        (
+            "uv sphere",
+            Mesh::from(shape::UVSphere {
+                radius: 2.0,
+                sectors: 12,
+                stacks: 6,
+            }),
+            13 * 7,
+        ),
+        (
+            "torus",
+            Mesh::from(shape::Torus {
+                radius: 1.0,
+                tube_radius: 0.25,
+                segments: 12,
+                tube_segments: 8,
+            }),
+            13 * 9,
+        ),
+        (
+            "cylinder",
+            Mesh::from(shape::Cylinder {
+                radius: 0.5,
+                height: 2.0,
+                segments: 12,
+            }),
+            // the side, then a center and a ring for each cap
+            13 * 2 + 14 * 2,
+        ),
+        (
+            "capsule",
+            Mesh::from(shape::Capsule {
+                radius: 0.5,
+                depth: 1.0,
+                segments: 12,
+                rings: 4,
+            }),
+            13 * 10,
+        ),
+        (
+            "cone",
+            Mesh::from(shape::Cone {
+                radius: 0.5,
+                height: 1.0,
+                segments: 12,
+            }),
+            13 * 2 + 14,
+        ),
+    ];
+
+    for (name, mesh, vertex_count) in shapes {
+        assert_eq!(mesh.vertex_count(), vertex_count, "{} vertex count", name);
+        assert!(
+            mesh.validate(IndexFormat::Uint16).is_ok(),
+            "{} is invalid",
+            name
+        );
+
+        let positions = mesh
+            .attribute::<[f32; 3]>(VertexAttribute::POSITION)
+            .unwrap();
+        let normals = mesh.attribute::<[f32; 3]>(VertexAttribute::NORMAL).unwrap();
+        for normal in normals.iter() {
+            let length = Vec3::from(*normal).length();
+            assert!(
+                (length - 1.0).abs() < 1e-5,
+                "{} normal of length {}",
+                name,
+                length
+            );
+        }
+        // triangles should wind counter-clockwise seen from the side the normals face
+        for triangle in mesh.indices.as_ref().unwrap().chunks_exact(3) {
+            let corner = |i: usize| Vec3::from(positions[triangle[i] as usize]);
+            let (a, b, c) = (corner(0), corner(1), corner(2));
+            let normal = triangle
+                .iter()
+                .map(|&i| Vec3::from(normals[i as usize]))
+                .fold(Vec3::zero(), |sum, normal| sum + normal);
+            assert!((b - a).cross(c - a).dot(normal) > 0.0, "{} winding", name);
+        }
+    }
+}
+
+#[test]
+fn test_transform_and_merge() {
+    use bevy::math::{Mat4, Vec3};
+    use bevy::render::color::Color;
+    use bevy::render::mesh::MergeMeshesError;
+
+    // mirrored and stretched, the triangles should still face the way the normals do
+    let mut mesh = Mesh::from(shape::Cube { size: 1.0 });
+    mesh.transform(&Mat4::from_scale(Vec3::new(-2.0, 1.0, 1.0)));
+    let positions = mesh
+        .attribute::<[f32; 3]>(VertexAttribute::POSITION)
+        .unwrap();
+    let normals = mesh.attribute::<[f32; 3]>(VertexAttribute::NORMAL).unwrap();
+    assert!(positions.iter().all(|p| p[0].abs() == 2.0));
+    for triangle in mesh.indices.as_ref().unwrap().chunks_exact(3) {
+        let corner = |i: usize| Vec3::from(positions[triangle[i] as usize]);
+        let (a, b, c) = (corner(0), corner(1), corner(2));
+        let normal = Vec3::from(normals[triangle[0] as usize]);
+        assert!((normal.length() - 1.0).abs() < 1e-5);
+        assert!((b - a).cross(c - a).dot(normal) > 0.0);
+    }
+
+    let mut other = Mesh::from(shape::Cube { size: 1.0 });
+    other.set_color(Color::rgb(1.0, 0.0, 0.0));
+    let merged = Mesh::merge(&[mesh, other]).unwrap();
+    assert_eq!(merged.vertex_count(), 48);
+    let indices = merged.indices.as_ref().unwrap();
+    assert_eq!(indices.len(), 72);
+    assert!(indices[36..].iter().all(|&i| i >= 24 && i < 48));
+    let colors = merged
+        .attribute::<[f32; 4]>(VertexAttribute::COLOR)
+        .unwrap();
+    assert_eq!(colors[24], [1.0, 0.0, 0.0, 1.0]);
+
+    let mut merged = merged;
+    merged.map_colors(|position, color| {
+        if position.y() > 0.0 {
+            Color::rgb(0.0, 0.0, 1.0)
+        } else {
+            color
+        }
+    });
+    let colors = merged
+        .attribute::<[f32; 4]>(VertexAttribute::COLOR)
+        .unwrap();
+    let positions = merged
+        .attribute::<[f32; 3]>(VertexAttribute::POSITION)
+        .unwrap();
+    for (color, position) in colors.iter().zip(positions.iter()) {
+        assert_eq!(position[1] > 0.0, *color == [0.0, 0.0, 1.0, 1.0]);
+    }
+
+    let mut missing_uvs = Mesh::from(shape::Cube { size: 1.0 });
+    missing_uvs.remove_attribute(VertexAttribute::UV);
+    match Mesh::merge(&[Mesh::from(shape::Cube { size: 1.0 }), missing_uvs]) {
+        Err(MergeMeshesError::MismatchedAttribute(name)) => {
+            assert_eq!(name, VertexAttribute::UV)
+        }
+        other => panic!("expected a mismatched attribute error, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_map_packed_colors() {
+    use bevy::render::color::Color;
+    use bevy::render::mesh::VertexAttributeValues;
+
+    let mut mesh = Mesh::from(shape::Cube { size: 1.0 });
+    mesh.insert_attribute(VertexAttribute {
+        name: VertexAttribute::COLOR.into(),
+        values: VertexAttributeValues::Uchar4Norm(vec![[255, 0, 0, 255]; 24]),
+    });
+    let mut seen = Vec::new();
+    mesh.map_colors(|position, color| {
+        seen.push(color);
+        if position.y() > 0.0 {
+            Color::rgb(0.0, 0.0, 1.0)
+        } else {
+            color
+        }
+    });
+    assert!(
+        seen.iter().all(|color| *color == Color::rgb(1.0, 0.0, 0.0)),
+        "the closure sees the stored colours, not white"
+    );
+
+    let colors = mesh
+        .attribute::<[u8; 4]>(VertexAttribute::COLOR)
+        .expect("packed colours stay packed");
+    let positions = mesh
+        .attribute::<[f32; 3]>(VertexAttribute::POSITION)
+        .unwrap();
+    for (color, position) in colors.iter().zip(positions.iter()) {
+        let expected = if position[1] > 0.0 {
+            [0, 0, 255, 255]
+        } else {
+            [255, 0, 0, 255]
+        };
+        assert_eq!(*color, expected);
+    }
+}
+
+#[test]
+fn test_bounds() {
+    use bevy::math::{Mat4, Quat, Vec3};
+
+    let cube = Mesh::from(shape::Cube { size: 1.0 });
+    let aabb = cube.compute_aabb().unwrap();
+    assert_eq!(aabb.min, Vec3::new(-1.0, -1.0, -1.0));
+    assert_eq!(aabb.max, Vec3::new(1.0, 1.0, 1.0));
+    let sphere = cube.compute_bounding_sphere().unwrap();
+    assert_eq!(sphere.center, Vec3::zero());
+    assert!((sphere.radius - 3.0f32.sqrt()).abs() < 1e-5);
+
+    let transform = Mat4::from_scale_rotation_translation(
+        Vec3::new(1.0, 3.0, 1.0),
+        Quat::identity(),
+        Vec3::new(10.0, 0.0, 0.0),
+    );
+    let moved = aabb.transformed(&transform);
+    assert_eq!(moved.min, Vec3::new(9.0, -3.0, -1.0));
+    assert_eq!(moved.max, Vec3::new(11.0, 3.0, 1.0));
+    let moved = sphere.transformed(&transform);
+    assert_eq!(moved.center, Vec3::new(10.0, 0.0, 0.0));
+    assert!((moved.radius - 3.0 * 3.0f32.sqrt()).abs() < 1e-4);
+
+    let planet = Mesh::from(shape::Icosphere {
+        radius: 5.0,
+        subdivisions: 3,
+        split_seam: false,
+    });
+    let sphere = planet.compute_bounding_sphere().unwrap();
+    assert!((sphere.radius - 5.0).abs() < 1e-3);
+
+    assert!(Mesh::new(PrimitiveTopology::TriangleList)
+        .compute_aabb()
+        .is_none());
+}
+
+#[test]
+fn test_generate_tangents() {
+    let mut mesh = Mesh::from(shape::Plane { size: 2.0 });
+    mesh.generate_tangents().unwrap();
+    let tangents = mesh
+        .attribute::<[f32; 4]>(VertexAttribute::TANGENT)
+        .expect("tangents should be Float4");
+    for tangent in tangents.iter() {
+        assert!((tangent[0] - 1.0).abs() < 1e-5, "tangent follows +u");
+        assert!(tangent[1].abs() < 1e-5 && tangent[2].abs() < 1e-5);
+        assert_eq!(tangent[3], 1.0);
+    }
+}
+
+#[test]
+fn test_icosphere_split_seam() {
+    let mesh = Mesh::from(shape::Icosphere {
+        radius: 1.0,
+        subdivisions: 4,
+        split_seam: true,
+    });
+    let uvs = mesh
+        .attribute::<[f32; 2]>(VertexAttribute::UV)
+        .expect("uvs should be Float2");
+    for triangle in mesh.indices.as_ref().unwrap().chunks_exact(3) {
+        let us = triangle.iter().map(|&i| uvs[i as usize][0]);
+        let max = us.clone().fold(std::f32::MIN, f32::max);
+        let min = us.fold(std::f32::MAX, f32::min);
+        assert!(
+            max - min <= 0.5,
+            "no triangle should stretch across the seam"
+        );
+    }
+}
+
+#[test]
+fn test_flat_shading() {
+    let mut mesh = Mesh::from(shape::Icosphere {
+        radius: 1.0,
+        subdivisions: 2,
+        split_seam: false,
+    });
+    let triangles = mesh.indices.as_ref().unwrap().len() / 3;
+    assert!(
+        mesh.compute_flat_normals().is_err(),
+        "indexed meshes can't be flat shaded"
+    );
+    mesh.duplicate_vertices();
+    mesh.compute_flat_normals().unwrap();
+    mesh.compute_face_colors().unwrap();
+    assert!(mesh.indices.is_none());
+    for attribute in mesh.attributes.iter() {
+        assert_eq!(attribute.values.len(), triangles * 3);
+    }
+    let normals = mesh
+        .attribute::<[f32; 3]>(VertexAttribute::NORMAL)
+        .expect("normals should be Float3");
+    for triangle in normals.chunks_exact(3) {
+        assert_eq!(triangle[0], triangle[1]);
+        assert_eq!(triangle[0], triangle[2]);
+        let length = triangle[0].iter().map(|n| n * n).sum::<f32>().sqrt();
+        assert!((length - 1.0).abs() < 1e-5);
+    }
+}
+
+#[test]
+fn test_named_attributes() {
+    let mut mesh = Mesh::from(shape::Cube { size: 1.0 });
+    assert!(mesh
+        .attribute::<[f32; 3]>(VertexAttribute::POSITION)
+        .is_some());
+    assert!(
+        mesh.attribute::<[f32; 2]>(VertexAttribute::POSITION)
+            .is_none(),
+        "wrong type gives nothing"
+    );
+
+    mesh.attribute_mut::<[f32; 4]>(VertexAttribute::COLOR)
+        .unwrap()[0] = [0.0; 4];
+    assert_eq!(
+        mesh.attribute::<[f32; 4]>(VertexAttribute::COLOR).unwrap()[0],
+        [0.0; 4]
+    );
+
+    let normal = mesh.remove_attribute(VertexAttribute::NORMAL).unwrap();
+    assert!(mesh
+        .attribute::<[f32; 3]>(VertexAttribute::NORMAL)
+        .is_none());
+    mesh.insert_attribute(normal);
+    mesh.insert_attribute(VertexAttribute::uv(vec![[1.0, 1.0]; 24]));
+    assert_eq!(
+        mesh.attributes.len(),
+        4,
+        "inserting a name that exists replaces it"
+    );
+    assert_eq!(
+        mesh.attribute::<[f32; 2]>(VertexAttribute::UV).unwrap()[0],
+        [1.0, 1.0]
+    );
+}
+
+#[test]
+fn test_validate() {
+    use bevy::render::mesh::MeshValidationError;
+    use bevy::render::pipeline::IndexFormat;
+
+    let mut mesh = Mesh::from(shape::Cube { size: 1.0 });
+    assert!(mesh.validate(IndexFormat::Uint16).is_ok());
+
+    mesh.attribute_mut::<[f32; 3]>(VertexAttribute::POSITION)
+        .unwrap()[3][1] = std::f32::NAN;
+    match mesh.validate(IndexFormat::Uint16) {
+        Err(MeshValidationError::NonFinitePosition { vertex: 3, .. }) => {}
+        other => panic!("expected a NaN position error, got {:?}", other),
+    }
+
+    let mut mesh = Mesh::from(shape::Cube { size: 1.0 });
+    mesh.indices.as_mut().unwrap().push(0);
+    match mesh.validate(IndexFormat::Uint16) {
+        Err(MeshValidationError::IncompleteTriangle(37)) => {}
+        other => panic!("expected an incomplete triangle error, got {:?}", other),
+    }
+
+    let mut mesh = Mesh::from(shape::Cube { size: 1.0 });
+    mesh.duplicate_vertices();
+    assert!(mesh.validate(IndexFormat::Uint16).is_ok());
+    let mut mesh = Mesh::from(shape::Cube { size: 1.0 });
+    mesh.indices = None;
+    match mesh.validate(IndexFormat::Uint16) {
+        Err(MeshValidationError::IncompleteTriangle(24)) => {}
+        other => panic!("expected an incomplete triangle error, got {:?}", other),
+    }
+
+    let mut mesh = Mesh::from(shape::Cube { size: 1.0 });
+    mesh.indices.as_mut().unwrap()[0] = 24;
+    match mesh.validate(IndexFormat::Uint32) {
+        Err(MeshValidationError::IndexOutOfRange { index: 24, .. }) => {}
+        other => panic!("expected an out of range error, got {:?}", other),
+    }
+    mesh.indices.as_mut().unwrap()[0] = 70000;
+    match mesh.validate(IndexFormat::Uint16) {
+        Err(MeshValidationError::IndexFormatOverflow { index: 70000, .. }) => {}
+        other => panic!("expected an index format error, got {:?}", other),
+    }
+
+    let mut mesh = Mesh::from(shape::Cube { size: 1.0 });
+    mesh.insert_attribute(VertexAttribute::uv(vec![[0.0, 0.0]; 3]));
+    match mesh.validate(IndexFormat::Uint16) {
+        Err(MeshValidationError::MismatchedAttributeLength { len: 3, .. }) => {}
+        other => panic!("expected an attribute length error, got {:?}", other),
+    }
+
+    // too many vertices for 16 bit indices
+    let mesh = Mesh::from(shape::Icosphere {
+        radius: 1.0,
+        subdivisions: 100,
+        split_seam: false,
+    });
+    assert!(mesh.vertex_count() > std::u16::MAX as usize);
+    assert_eq!(mesh.index_format(), IndexFormat::Uint32);
+    assert!(mesh.validate(mesh.index_format()).is_ok());
+    assert_eq!(
+        Mesh::from(shape::Cube { size: 1.0 }).index_format(),
+        IndexFormat::Uint16
+    );
+}
+
+#[test]
+fn test_vertex_format_conversions() {
+    use bevy::render::mesh::{f16_to_f32, f32_to_f16, VertexAttributeValues};
+    use bevy::render::pipeline::VertexFormat;
+
+    let colors = VertexAttributeValues::Float4(vec![[0.0, 0.5, 1.0, 2.0]]);
+    match colors.convert(VertexFormat::Uchar4Norm) {
+        Some(VertexAttributeValues::Uchar4Norm(values)) => {
+            assert_eq!(values, vec![[0, 128, 255, 255]])
+        }
+        other => panic!("expected Uchar4Norm, got {:?}", other),
+    }
+    assert_eq!(
+        colors.get_bytes_as(VertexFormat::Uchar4Norm).unwrap().len(),
+        4
+    );
+    assert!(colors.get_bytes_as(VertexFormat::Float2).is_none());
+
+    let uvs = VertexAttributeValues::Float2(vec![[-1.0, 0.25]]);
+    let packed = uvs.convert(VertexFormat::Short2Norm).unwrap();
+    match packed.convert(VertexFormat::Float2) {
+        Some(VertexAttributeValues::Float2(values)) => {
+            assert_eq!(values[0][0], -1.0);
+            assert!((values[0][1] - 0.25).abs() < 1e-4);
+        }
+        other => panic!("expected Float2, got {:?}", other),
+    }
+
+    for &value in &[0.0, 1.0, -2.5, 0.333, 65504.0, 1e-6] {
+        let half = f16_to_f32(f32_to_f16(value));
+        assert!(
+            (half - value).abs() <= value.abs() * 1e-3 + 1e-7,
+            "{} came back as {}",
+            value,
+            half
+        );
+    }
+    assert_eq!(f32_to_f16(1.0), 0x3c00);
+    assert_eq!(f16_to_f32(0x0001), 2.0f32.powi(-24));
+    assert!(f16_to_f32(f32_to_f16(1e6)).is_infinite());
+}
+
+#[test]
+fn test_custom_vertex_layout() {
+    use bevy::render::mesh::{mesh_attribute_name, VertexAttributeValues};
+    use bevy::render::pipeline::{
+        InputStepMode, VertexAttributeDescriptor, VertexBufferDescriptor, VertexFormat,
+    };
+
+    assert_eq!(
+        mesh_attribute_name("Vertex", "Vertex_Position"),
+        "Vertex_Position"
+    );
+    assert_eq!(
+        mesh_attribute_name("PlanetVertex", "PlanetVertex_Elevation"),
+        "Vertex_Elevation"
+    );
+
+    let mut mesh = Mesh::from(shape::Plane { size: 1.0 });
+    mesh.insert_attribute(VertexAttribute {
+        name: "Vertex_Elevation".into(),
+        values: VertexAttributeValues::Float(vec![2.0; 4]),
+    });
+    let descriptor = VertexBufferDescriptor {
+        name: "PlanetVertex".into(),
+        stride: 4,
+        step_mode: InputStepMode::Vertex,
+        attributes: vec![VertexAttributeDescriptor {
+            name: "PlanetVertex_Elevation".into(),
+            offset: 0,
+            format: VertexFormat::Float,
+            shader_location: 0,
+        }],
+    };
+    let bytes = mesh.get_vertex_buffer_bytes(&descriptor, false).unwrap();
+    assert_eq!(bytes.as_slice(), vec![2.0f32; 4].as_slice().as_bytes());
+}
+
+#[test]
+fn test_dirty_vertices() {
+    use bevy::render::color::Color;
+
+    let mut mesh = Mesh::from(shape::Cube { size: 1.0 });
+    assert_eq!(
+        mesh.dirty_vertices.take(),
+        None,
+        "new meshes are uploaded whole"
+    );
+
+    for color in mesh
+        .attribute_range_mut::<[f32; 4]>(VertexAttribute::COLOR, 4..8)
+        .unwrap()
+    {
+        *color = [0.0; 4];
+    }
+    mesh.dirty_vertices.mark(10..12);
+    mesh.dirty_vertices.mark(6..6);
+    assert_eq!(mesh.dirty_vertices.take(), Some(4..12), "ranges merge");
+    assert_eq!(mesh.dirty_vertices.take(), None, "taking leaves it clean");
+
+    assert!(mesh
+        .attribute_range_mut::<[f32; 4]>(VertexAttribute::COLOR, 20..30)
+        .is_none());
+    assert_eq!(
+        mesh.dirty_vertices.take(),
+        None,
+        "out of range writes mark nothing"
+    );
+
+    mesh.attribute_range_mut::<[f32; 4]>(VertexAttribute::COLOR, 4..8);
+    mesh.set_color(Color::rgb(1.0, 0.0, 0.0));
+    assert_eq!(
+        mesh.dirty_vertices.take(),
+        Some(0..24),
+        "whole attribute changes cover a pending range"
+    );
+}
+
+#[test]
+fn test_mismatched_type_marks_nothing() {
+    use bevy::{math::Vec3, render::mesh::VertexAttributeValues};
+
+    let mut mesh = Mesh::from(shape::Cube { size: 1.0 });
+    let bounds = mesh.bounds();
+    // written behind the cache's back, a recompute would pick it up
+    let positions = mesh
+        .attributes
+        .iter_mut()
+        .find(|attribute| attribute.name == VertexAttribute::POSITION)
+        .unwrap();
+    if let VertexAttributeValues::Float3(positions) = &mut positions.values {
+        positions[0] = [5.0, 5.0, 5.0];
+    }
+
+    assert!(mesh
+        .attribute_mut::<[f32; 2]>(VertexAttribute::POSITION)
+        .is_none());
+    assert!(mesh
+        .attribute_range_mut::<[f32; 2]>(VertexAttribute::POSITION, 0..4)
+        .is_none());
+    assert_eq!(mesh.dirty_vertices.take(), None);
+    assert_eq!(mesh.bounds(), bounds, "the cached bounds are kept");
+
+    mesh.attribute_mut::<[f32; 3]>(VertexAttribute::POSITION);
+    assert_eq!(mesh.dirty_vertices.take(), Some(0..24));
+    assert_eq!(mesh.bounds().unwrap().aabb.max, Vec3::new(5.0, 5.0, 5.0));
+}
+
+#[test]
+fn test_mirror_rewrites_indices() {
+    use bevy::math::{Mat4, Vec3};
+    use bevy::render::pipeline::IndexFormat;
+
+    let mut mesh = Mesh::from(shape::Cube { size: 1.0 });
+    let mut expected = mesh.indices.clone().unwrap();
+    for triangle in expected.chunks_exact_mut(3) {
+        triangle.swap(1, 2);
+    }
+    // a pending partial update must not survive the new winding
+    mesh.dirty_vertices.mark(0..4);
+    mesh.transform(&Mat4::from_scale(Vec3::new(-1.0, 1.0, 1.0)));
+    assert_eq!(
+        mesh.dirty_vertices.take(),
+        None,
+        "indexed meshes are uploaded whole"
+    );
+    let expected = expected.iter().map(|&i| i as u16).collect::<Vec<u16>>();
+    assert_eq!(
+        mesh.get_index_buffer_bytes(IndexFormat::Uint16).unwrap(),
+        expected.as_slice().as_bytes()
+    );
+
+    let mut mesh = Mesh::from(shape::Cube { size: 1.0 });
+    mesh.duplicate_vertices();
+    mesh.transform(&Mat4::from_scale(Vec3::new(-1.0, 1.0, 1.0)));
+    assert_eq!(
+        mesh.dirty_vertices.take(),
+        Some(0..36),
+        "without indices the vertices carry the winding"
+    );
+}
+
+#[test]
+fn test_cached_bounds() {
+    use bevy::math::{Mat4, Vec3};
+
+    let mut mesh = Mesh::from(shape::Cube { size: 1.0 });
+    assert_eq!(mesh.bounds(), mesh.compute_bounds());
+
+    for position in mesh
+        .attribute_mut::<[f32; 3]>(VertexAttribute::POSITION)
+        .unwrap()
+    {
+        position[1] *= 2.0;
+    }
+    assert_eq!(mesh.bounds().unwrap().aabb.max, Vec3::new(1.0, 2.0, 1.0));
+
+    mesh.transform(&Mat4::from_translation(Vec3::new(10.0, 0.0, 0.0)));
+    assert_eq!(mesh.bounds().unwrap().aabb.min, Vec3::new(9.0, -2.0, -1.0));
+
+    let other = Mesh::from(shape::Cube { size: 1.0 });
+    let mut merged = Mesh::merge(&[mesh, other]).unwrap();
+    let bounds = merged.bounds().unwrap();
+    assert_eq!(bounds.aabb, merged.compute_aabb().unwrap());
+    let exact = merged.compute_bounding_sphere().unwrap();
+    assert_eq!(bounds.sphere.center, exact.center);
+    assert!(bounds.sphere.radius >= exact.radius);
+
+    merged.remove_attribute(VertexAttribute::POSITION);
+    assert_eq!(merged.bounds(), None);
+}
+
+#[test]
+fn test_tangents_on_uv_quad() {
+    use bevy::math::Vec3;
+
+    // a quad in the xy plane with u along +x and v along -y, its normals bent outwards so
+    // the tangents can't just be the edge directions
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.insert_attribute(VertexAttribute::position(vec![
+        [-1.0, -1.0, 0.0],
+        [1.0, -1.0, 0.0],
+        [1.0, 1.0, 0.0],
+        [-1.0, 1.0, 0.0],
+    ]));
+    let normals = [
+        [-0.3, -0.3, 1.0],
+        [0.3, -0.3, 1.0],
+        [0.3, 0.3, 1.0],
+        [-0.3, 0.3, 1.0],
+    ]
+    .iter()
+    .map(|n| Vec3::from(*n).normalize().into())
+    .collect::<Vec<[f32; 3]>>();
+    mesh.insert_attribute(VertexAttribute::normal(normals.clone()));
+    mesh.insert_attribute(VertexAttribute::uv(vec![
+        [0.0, 1.0],
+        [1.0, 1.0],
+        [1.0, 0.0],
+        [0.0, 0.0],
+    ]));
+    mesh.indices = Some(vec![0, 1, 2, 0, 2, 3]);
+
+    mesh.generate_tangents().unwrap();
+    let tangents = mesh
+        .attribute::<[f32; 4]>(VertexAttribute::TANGENT)
+        .unwrap();
+    for (tangent, normal) in tangents.iter().zip(normals.iter()) {
+        let direction = Vec3::new(tangent[0], tangent[1], tangent[2]);
+        assert!((direction.length() - 1.0).abs() < 1e-5, "unit length");
+        assert!(
+            direction.dot(Vec3::from(*normal)).abs() < 1e-5,
+            "orthogonal"
+        );
+        assert!(direction.x() > 0.0, "follows +u");
+        assert_eq!(tangent[3].abs(), 1.0);
+    }
+}
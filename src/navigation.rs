@@ -0,0 +1,359 @@
+use crate::{geo, surface::Biome};
+use bevy::{prelude::*, render::mesh::VertexAttribute};
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashMap},
+};
+
+/// Prices a single step between two neighbouring vertices, `None` means the step can't be
+/// taken. Positions are in planet space. Any `Fn(Vec3, Vec3) -> Option<f32>` works too.
+pub trait PathCost {
+    fn cost(&self, from: Vec3, to: Vec3) -> Option<f32>;
+}
+
+impl<F: Fn(Vec3, Vec3) -> Option<f32>> PathCost for F {
+    fn cost(&self, from: Vec3, to: Vec3) -> Option<f32> {
+        self(from, to)
+    }
+}
+
+/// Length of the great circle arc between two points, measured at their average radius.
+pub fn arc_length(from: Vec3, to: Vec3) -> f32 {
+    geo::central_angle(from, to) * (from.length() + to.length()) * 0.5
+}
+
+/// The usual way to get around a planet: arc length, made more expensive by slope and by
+/// the biome being walked into.
+pub struct TerrainCost {
+    /// radius of the ocean surface, see `PlanetConfig::sea_level_radius`
+    pub sea_level: f32,
+    /// rise over run, anything steeper can't be walked
+    pub max_slope: f32,
+    /// extra cost per unit of slope, 0.0 ignores slope below `max_slope`
+    pub slope_weight: f32,
+    /// cost multiplier on land
+    pub land: f32,
+    /// cost multiplier for stepping into the ocean, `None` keeps paths out of the water
+    pub water: Option<f32>,
+}
+
+impl TerrainCost {
+    pub fn new(sea_level: f32) -> Self {
+        Self {
+            sea_level,
+            max_slope: 1.0,
+            slope_weight: 2.0,
+            land: 1.0,
+            water: None,
+        }
+    }
+
+    pub fn biome(&self, biome: Biome) -> Option<f32> {
+        match biome {
+            Biome::Land => Some(self.land),
+            Biome::Ocean => self.water,
+        }
+    }
+}
+
+impl PathCost for TerrainCost {
+    fn cost(&self, from: Vec3, to: Vec3) -> Option<f32> {
+        let run = arc_length(from, to);
+        let slope = (to.length() - from.length()).abs() / run.max(std::f32::EPSILON);
+        if slope > self.max_slope {
+            return None;
+        }
+        let biome = self.biome(Biome::classify(to.length() - self.sea_level))?;
+        Some(run * biome * (1.0 + slope * self.slope_weight))
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Path {
+    /// graph vertices from start to goal, both included
+    pub vertices: Vec<u32>,
+    /// planet space positions of `vertices`
+    pub points: Vec<Vec3>,
+    pub cost: f32,
+}
+
+impl Path {
+    /// Walking length along the ground, following great circles between the vertices.
+    pub fn length(&self) -> f32 {
+        self.points.windows(2).map(|w| arc_length(w[0], w[1])).sum()
+    }
+
+    /// Points no more than `spacing` apart. Between vertices they follow the great circle
+    /// with the radius blended linearly, so they don't cut through the planet like a chord.
+    pub fn resample(&self, spacing: f32) -> Vec<Vec3> {
+        let mut points = Vec::new();
+        for w in self.points.windows(2) {
+            let steps = (arc_length(w[0], w[1]) / spacing).ceil().max(1.0) as usize;
+            let (r0, r1) = (w[0].length(), w[1].length());
+            for step in 0..steps {
+                let t = step as f32 / steps as f32;
+                points.push(geo::slerp(w[0], w[1], t) * (r0 + (r1 - r0) * t));
+            }
+        }
+        points.extend(self.points.last());
+        points
+    }
+}
+
+// min-heap entry for the open set
+#[derive(PartialEq)]
+struct Open {
+    priority: f32,
+    vertex: u32,
+}
+
+impl Eq for Open {}
+
+impl PartialOrd for Open {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Open {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .priority
+            .partial_cmp(&self.priority)
+            .unwrap_or(Ordering::Equal)
+    }
+}
+
+/// Which vertices of a mesh are connected, built from its index buffer. Vertices at the same
+/// position are welded, so flat shaded and tiled meshes give the same graph as smooth ones.
+pub struct NavGraph {
+    positions: Vec<Vec3>,
+    neighbours: Vec<Vec<u32>>,
+    /// maps a mesh vertex index to its welded graph vertex
+    remap: Vec<u32>,
+    /// closest any vertex gets to the center, keeps the A* heuristic from overestimating
+    min_radius: f32,
+}
+
+impl NavGraph {
+    /// `None` if the mesh has no `Float3` positions.
+    pub fn from_mesh(mesh: &Mesh) -> Option<Self> {
+        let mesh_positions = mesh.attribute::<[f32; 3]>(VertexAttribute::POSITION)?;
+        let indices = mesh.triangle_indices();
+
+        let mut welded = HashMap::new();
+        let mut positions = Vec::new();
+        let remap = mesh_positions
+            .iter()
+            .map(|p| {
+                let key = [p[0].to_bits(), p[1].to_bits(), p[2].to_bits()];
+                *welded.entry(key).or_insert_with(|| {
+                    positions.push(Vec3::from(*p));
+                    positions.len() as u32 - 1
+                })
+            })
+            .collect::<Vec<u32>>();
+
+        let mut neighbours = vec![Vec::new(); positions.len()];
+        for tri in indices.chunks_exact(3) {
+            for (a, b) in [(tri[0], tri[1]), (tri[1], tri[2]), (tri[2], tri[0])].iter() {
+                let (a, b) = (remap[*a as usize], remap[*b as usize]);
+                if a == b {
+                    continue;
+                }
+                if !neighbours[a as usize].contains(&b) {
+                    neighbours[a as usize].push(b);
+                }
+                if !neighbours[b as usize].contains(&a) {
+                    neighbours[b as usize].push(a);
+                }
+            }
+        }
+
+        let min_radius = positions
+            .iter()
+            .map(|p| p.length())
+            .fold(std::f32::MAX, f32::min);
+        Some(Self {
+            positions,
+            neighbours,
+            remap,
+            min_radius,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    pub fn position(&self, vertex: u32) -> Vec3 {
+        self.positions[vertex as usize]
+    }
+
+    pub fn neighbours(&self, vertex: u32) -> &[u32] {
+        &self.neighbours[vertex as usize]
+    }
+
+    /// The graph vertex a mesh vertex was welded into, e.g. for `RayHit::vertices`.
+    pub fn vertex_of(&self, mesh_vertex: u32) -> u32 {
+        self.remap[mesh_vertex as usize]
+    }
+
+    /// The vertex closest to `direction` from the planet center.
+    pub fn nearest(&self, direction: Vec3) -> u32 {
+        let direction = direction.normalize();
+        let mut nearest = 0;
+        let mut best = std::f32::MIN;
+        for (vertex, position) in self.positions.iter().enumerate() {
+            let d = position.normalize().dot(direction);
+            if d > best {
+                best = d;
+                nearest = vertex as u32;
+            }
+        }
+        nearest
+    }
+
+    /// A* from `start` to `goal`, `None` if the goal can't be reached. The heuristic is the
+    /// great circle distance at the lowest point of the planet, so it never overestimates
+    /// as long as `cost` charges at least the arc length of a step.
+    pub fn find_path(&self, start: u32, goal: u32, cost: &impl PathCost) -> Option<Path> {
+        let goal_position = self.position(goal);
+        let heuristic = |vertex: u32| {
+            geo::great_circle_distance(self.position(vertex), goal_position, self.min_radius)
+        };
+        let (costs, previous) = self.search(start, Some(goal), cost, heuristic);
+        if costs[goal as usize].is_infinite() {
+            return None;
+        }
+
+        let mut vertices = vec![goal];
+        let mut vertex = goal;
+        while vertex != start {
+            vertex = previous[vertex as usize];
+            vertices.push(vertex);
+        }
+        vertices.reverse();
+        Some(Path {
+            points: vertices.iter().map(|v| self.position(*v)).collect(),
+            vertices,
+            cost: costs[goal as usize],
+        })
+    }
+
+    /// Dijkstra from `start` to every vertex, unreachable ones are infinite. Handy as a flow
+    /// field when lots of units head for the same place.
+    pub fn costs_from(&self, start: u32, cost: &impl PathCost) -> Vec<f32> {
+        self.search(start, None, cost, |_| 0.0).0
+    }
+
+    fn search(
+        &self,
+        start: u32,
+        goal: Option<u32>,
+        cost: &impl PathCost,
+        heuristic: impl Fn(u32) -> f32,
+    ) -> (Vec<f32>, Vec<u32>) {
+        let mut costs = vec![std::f32::INFINITY; self.len()];
+        let mut previous = vec![start; self.len()];
+        let mut closed = vec![false; self.len()];
+        let mut open = BinaryHeap::new();
+        costs[start as usize] = 0.0;
+        open.push(Open {
+            priority: heuristic(start),
+            vertex: start,
+        });
+
+        while let Some(Open { vertex, .. }) = open.pop() {
+            if closed[vertex as usize] {
+                continue;
+            }
+            closed[vertex as usize] = true;
+            if Some(vertex) == goal {
+                break;
+            }
+            let from = self.position(vertex);
+            for &next in self.neighbours(vertex) {
+                if closed[next as usize] {
+                    continue;
+                }
+                let step = match cost.cost(from, self.position(next)) {
+                    Some(step) => step,
+                    None => continue,
+                };
+                let total = costs[vertex as usize] + step;
+                if total < costs[next as usize] {
+                    costs[next as usize] = total;
+                    previous[next as usize] = vertex;
+                    open.push(Open {
+                        priority: total + heuristic(next),
+                        vertex: next,
+                    });
+                }
+            }
+        }
+        (costs, previous)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{arc_length, NavGraph};
+    use bevy::{prelude::*, render::mesh::shape};
+
+    fn graph() -> NavGraph {
+        NavGraph::from_mesh(&Mesh::from(shape::Icosphere {
+            radius: 1.0,
+            subdivisions: 4,
+            split_seam: false,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_astar_matches_dijkstra() {
+        let graph = graph();
+        // uphill towards the north pole costs more, but never less than the arc length
+        let cost = |from: Vec3, to: Vec3| {
+            Some(arc_length(from, to) * (1.0 + (to.y() - from.y()).max(0.0) * 4.0))
+        };
+        let start = graph.nearest(Vec3::new(1.0, -0.2, 0.0));
+        let costs = graph.costs_from(start, &cost);
+        for goal in (0..graph.len() as u32).step_by(37) {
+            let path = graph.find_path(start, goal, &cost).unwrap();
+            assert_eq!(path.vertices.first(), Some(&start));
+            assert_eq!(path.vertices.last(), Some(&goal));
+            assert!(
+                (path.cost - costs[goal as usize]).abs() < 1e-4,
+                "A* found {} but Dijkstra {} to {}",
+                path.cost,
+                costs[goal as usize],
+                goal
+            );
+        }
+    }
+
+    #[test]
+    fn test_impassable() {
+        let graph = graph();
+        let start = graph.nearest(Vec3::new(0.0, -1.0, 0.0));
+        let goal = graph.nearest(Vec3::new(0.0, 1.0, 0.0));
+        let blocked = |_: Vec3, _: Vec3| -> Option<f32> { None };
+        assert!(graph.find_path(start, goal, &blocked).is_none());
+
+        // a wall around the equator cuts the poles off from each other
+        let wall = |from: Vec3, to: Vec3| {
+            if to.y().abs() < 0.1 || from.y().signum() != to.y().signum() {
+                None
+            } else {
+                Some(arc_length(from, to))
+            }
+        };
+        assert!(graph.find_path(start, goal, &wall).is_none());
+        assert!(graph.costs_from(start, &wall)[goal as usize].is_infinite());
+    }
+}
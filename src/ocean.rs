@@ -0,0 +1,187 @@
+use crate::{planet::PlanetConfig, shaders::glsl, wasd_camera::CameraMarker};
+use bevy::{
+    prelude::*,
+    render::{
+        mesh::{shape, VertexAttribute, VertexAttributeValues},
+        pipeline::{
+            DynamicBinding, InputStepMode, PipelineDescriptor, PipelineSpecialization,
+            RenderPipeline, VertexAttributeDescriptor, VertexBufferDescriptor,
+            VertexBufferDescriptors, VertexFormat,
+        },
+        render_graph::{base, AssetRenderResourcesNode, RenderGraph},
+        renderer::RenderResources,
+        shader::{ShaderStage, ShaderStages},
+    },
+};
+
+#[derive(RenderResources)]
+pub struct OceanMaterial {
+    pub shallow_color: Color,
+    pub deep_color: Color,
+    pub depth_scale: f32,
+    pub wave_strength: f32,
+    pub time: f32,
+    pub sun_position: Vec4,
+    pub camera_pos: Mat4,
+}
+
+impl OceanMaterial {
+    pub fn new(config: &PlanetConfig, sun_position: Vec4) -> Self {
+        Self {
+            shallow_color: config.ocean.shallow_color,
+            deep_color: config.ocean.deep_color,
+            depth_scale: config.ocean.depth_scale,
+            wave_strength: config.ocean.wave_strength,
+            time: 0.0,
+            sun_position,
+            camera_pos: Mat4::identity(),
+        }
+    }
+}
+
+/// Mesh attribute holding the depth of the water under each vertex as a `Float`.
+pub const DEPTH_ATTRIBUTE: &str = "Vertex_Depth";
+
+/// The ocean shader's own vertex buffer next to the usual `Vertex` one, just the depth.
+pub fn ocean_vertex_layout() -> VertexBufferDescriptor {
+    VertexBufferDescriptor {
+        name: "OceanVertex".into(),
+        stride: VertexFormat::Float.get_size(),
+        step_mode: InputStepMode::Vertex,
+        attributes: vec![VertexAttributeDescriptor {
+            name: "OceanVertex_Depth".into(),
+            offset: 0,
+            format: VertexFormat::Float,
+            shader_location: 4,
+        }],
+    }
+}
+
+pub fn register_ocean_vertex_layout(
+    mut vertex_buffer_descriptors: ResMut<VertexBufferDescriptors>,
+) {
+    vertex_buffer_descriptors.set(ocean_vertex_layout());
+}
+
+/// A sphere at sea level. The water depth under each vertex is sampled from the terrain
+/// into `DEPTH_ATTRIBUTE`.
+pub fn ocean_mesh(config: &PlanetConfig) -> Mesh {
+    let terrain = config.terrain();
+    let sea_level = config.sea_level_radius();
+    let mut mesh = Mesh::from(shape::Icosphere {
+        radius: sea_level,
+        subdivisions: config.ocean.subdivisions,
+        split_seam: false,
+    });
+
+    let depth = mesh
+        .attribute::<[f32; 3]>(VertexAttribute::POSITION)
+        .unwrap()
+        .iter()
+        .map(|verts| sea_level - terrain.height(Vec3::from(*verts)))
+        .collect();
+    mesh.insert_attribute(VertexAttribute {
+        name: DEPTH_ATTRIBUTE.into(),
+        values: VertexAttributeValues::Float(depth),
+    });
+    mesh
+}
+
+/// Sets up the ocean shaders and render graph node, returning the pipelines to put on the ocean.
+pub fn ocean_pipelines(
+    pipelines: &mut Assets<PipelineDescriptor>,
+    shaders: &mut Assets<Shader>,
+    render_graph: &mut RenderGraph,
+) -> RenderPipelines {
+    let pipeline_handle = pipelines.add(PipelineDescriptor::default_config(ShaderStages {
+        vertex: shaders.add(Shader::from_glsl(
+            ShaderStage::Vertex,
+            include_str!("../assets/ocean_shader.vert"),
+        )),
+        fragment: Some(shaders.add(Shader::from_glsl(
+            ShaderStage::Fragment,
+            &glsl(include_str!("../assets/ocean_shader.frag")),
+        ))),
+    }));
+
+    render_graph.add_system_node(
+        "ocean_material",
+        AssetRenderResourcesNode::<OceanMaterial>::new(true),
+    );
+    render_graph
+        .add_node_edge("ocean_material", base::node::MAIN_PASS)
+        .unwrap();
+
+    RenderPipelines::from_pipelines(vec![RenderPipeline::specialized(
+        pipeline_handle,
+        PipelineSpecialization {
+            dynamic_bindings: vec![
+                // Transform
+                DynamicBinding {
+                    bind_group: 1,
+                    binding: 0,
+                },
+                // OceanMaterial_shallow_color
+                DynamicBinding {
+                    bind_group: 1,
+                    binding: 1,
+                },
+                // OceanMaterial_deep_color
+                DynamicBinding {
+                    bind_group: 1,
+                    binding: 2,
+                },
+                // OceanMaterial_depth_scale
+                DynamicBinding {
+                    bind_group: 1,
+                    binding: 3,
+                },
+                // OceanMaterial_wave_strength
+                DynamicBinding {
+                    bind_group: 1,
+                    binding: 4,
+                },
+                // OceanMaterial_time
+                DynamicBinding {
+                    bind_group: 1,
+                    binding: 5,
+                },
+                // OceanMaterial_sun_position
+                DynamicBinding {
+                    bind_group: 1,
+                    binding: 6,
+                },
+                // OceanMaterial_camera_pos
+                DynamicBinding {
+                    bind_group: 1,
+                    binding: 7,
+                },
+            ],
+            ..Default::default()
+        },
+    )])
+}
+
+pub fn update_ocean(
+    time: Res<Time>,
+    config: Res<PlanetConfig>,
+    mut materials: ResMut<Assets<OceanMaterial>>,
+    mut query: Query<(&Transform, &CameraMarker)>,
+) {
+    for (trans, _cam) in &mut query.iter() {
+        let mut handles = Vec::new();
+        for mat in materials.iter() {
+            handles.push(mat.0);
+        }
+        for handle in handles {
+            if let Some(mat) = materials.get_mut(&handle) {
+                mat.time = time.seconds_since_startup as f32;
+                mat.camera_pos = trans.value;
+                mat.shallow_color = config.ocean.shallow_color;
+                mat.deep_color = config.ocean.deep_color;
+                mat.depth_scale = config.ocean.depth_scale;
+                mat.wave_strength = config.ocean.wave_strength;
+            }
+        }
+    }
+}
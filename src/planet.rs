@@ -0,0 +1,365 @@
+use crate::surface::Biome;
+use bevy::{
+    prelude::*,
+    render::{
+        mesh::{shape, VertexAttribute, VertexAttributeValues},
+        pipeline::{
+            InputStepMode, VertexAttributeDescriptor, VertexBufferDescriptor,
+            VertexBufferDescriptors, VertexFormat,
+        },
+    },
+};
+use noise::*;
+
+/// Marks the entity carrying the planet's terrain mesh.
+pub struct Planet;
+
+pub struct CloudConfig {
+    /// fraction of the sky covered, 0.0 is clear and 1.0 is overcast
+    pub coverage: f32,
+    /// height of the cloud shell above the highest terrain
+    pub altitude: f32,
+    /// wind speed at the equator in radians per second
+    pub speed: f32,
+    pub color: Color,
+    pub subdivisions: usize,
+}
+
+impl Default for CloudConfig {
+    fn default() -> Self {
+        Self {
+            coverage: 0.45,
+            altitude: 1500.0,
+            speed: 0.01,
+            color: Color::rgba(1.0, 1.0, 1.0, 0.9),
+            subdivisions: 12,
+        }
+    }
+}
+
+pub struct OceanConfig {
+    pub subdivisions: usize,
+    pub shallow_color: Color,
+    pub deep_color: Color,
+    /// water depth at which the ocean reaches `deep_color`
+    pub depth_scale: f32,
+    /// how far the animated waves tilt the surface normal
+    pub wave_strength: f32,
+}
+
+impl Default for OceanConfig {
+    fn default() -> Self {
+        Self {
+            subdivisions: 40,
+            shallow_color: Color::rgba(0.1, 0.6, 0.6, 0.6),
+            deep_color: Color::rgba(0.0, 0.05, 0.3, 0.95),
+            depth_scale: 6000.0,
+            wave_strength: 0.15,
+        }
+    }
+}
+
+pub struct ScatterConfig {
+    pub seed: u64,
+    /// placements are grouped into cubes of this size, each cube is one streaming unit
+    pub cell_size: f32,
+    /// cells closer than this to the camera are spawned
+    pub stream_radius: f32,
+}
+
+impl Default for ScatterConfig {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            cell_size: 5000.0,
+            stream_radius: 15000.0,
+        }
+    }
+}
+
+/// Domain warping, pushes the terrain sample direction around with vector noise before the
+/// elevation is looked up. Gives swirly coastlines and eroded looking ridges.
+pub struct WarpConfig {
+    /// how far the sample direction can be pushed, on the unit sphere. 0.0 turns warping off
+    pub strength: f32,
+    /// frequency of the warp noise on the unit sphere
+    pub frequency: f64,
+    pub octaves: usize,
+    /// each layer warps the already warped direction again
+    pub layers: usize,
+    pub seed: u32,
+}
+
+impl Default for WarpConfig {
+    fn default() -> Self {
+        Self {
+            strength: 0.15,
+            frequency: 1.5,
+            octaves: 4,
+            layers: 2,
+            seed: 0,
+        }
+    }
+}
+
+pub struct PlanetConfig {
+    /// radius of the icosphere before the terrain is applied
+    pub radius: f32,
+    pub subdivisions: usize,
+    /// lowest sea floor and highest peak, as a fraction of `radius`
+    pub elevation: (f32, f32),
+    /// frequency of the elevation noise on the unit sphere, so the size of the continents
+    /// doesn't depend on `radius`
+    pub elevation_frequency: f64,
+    /// radius of the ocean surface, as a fraction of `radius`
+    pub sea_level: f32,
+    /// share of the surface that should end up above water. When set, `solve_sea_level`
+    /// replaces `sea_level` with whatever gives this much land
+    pub land_fraction: Option<f32>,
+    pub atmo_radius: f32,
+    /// low-poly look: every triangle gets its own vertices, a face normal and one colour
+    pub flat_shaded: bool,
+    /// build the planet from hexagon tiles, see `tiles::HexGrid`
+    pub hex_tiles: bool,
+    pub warp: WarpConfig,
+    pub clouds: CloudConfig,
+    pub ocean: OceanConfig,
+    pub scatter: ScatterConfig,
+}
+
+impl Default for PlanetConfig {
+    fn default() -> Self {
+        Self {
+            radius: 50000.0,
+            subdivisions: 20,
+            elevation: (0.5, 0.8),
+            elevation_frequency: 1.5,
+            sea_level: 0.7,
+            land_fraction: Some(0.35),
+            atmo_radius: 45000.0,
+            flat_shaded: false,
+            hex_tiles: false,
+            warp: WarpConfig::default(),
+            clouds: CloudConfig::default(),
+            ocean: OceanConfig::default(),
+            scatter: ScatterConfig::default(),
+        }
+    }
+}
+
+impl PlanetConfig {
+    pub fn terrain(&self) -> TerrainSampler {
+        // three independent noise fields per layer make up one vector field
+        let warp = (0..self.warp.layers as u32 * 3)
+            .map(|i| {
+                Fbm::new()
+                    .set_seed(self.warp.seed.wrapping_add(i))
+                    .set_octaves(self.warp.octaves)
+                    .set_frequency(self.warp.frequency)
+            })
+            .collect();
+        TerrainSampler {
+            noise: RidgedMulti::new().set_frequency(self.elevation_frequency),
+            warp,
+            warp_strength: self.warp.strength,
+            radius: self.radius,
+            elevation: self.elevation,
+        }
+    }
+
+    /// Picks `sea_level` from `land_fraction` using the heights at the planet mesh's vertices.
+    /// Does nothing without a `land_fraction`.
+    pub fn solve_sea_level(&mut self) {
+        let land_fraction = match self.land_fraction {
+            Some(land_fraction) => land_fraction,
+            None => return,
+        };
+        let terrain = self.terrain();
+        let sphere = Mesh::from(shape::Icosphere {
+            radius: 1.0,
+            subdivisions: self.subdivisions,
+            split_seam: false,
+        });
+        let heights = sphere
+            .attribute::<[f32; 3]>(VertexAttribute::POSITION)
+            .unwrap()
+            .iter()
+            .map(|p| terrain.height(Vec3::from(*p)))
+            .collect::<Vec<_>>();
+        let (low, high) = (
+            self.radius * self.elevation.0,
+            self.radius * self.elevation.1,
+        );
+        self.sea_level = level_for_fraction(&heights, land_fraction, (low, high)) / self.radius;
+    }
+
+    pub fn sea_level_radius(&self) -> f32 {
+        self.radius * self.sea_level
+    }
+
+    pub fn cloud_radius(&self) -> f32 {
+        self.radius * self.elevation.1 + self.clouds.altitude
+    }
+}
+
+/// The height function the planet mesh is built from. Anything that needs to know where
+/// the ground is (ocean depth, gameplay queries) should go through this.
+pub struct TerrainSampler {
+    noise: RidgedMulti,
+    /// x, y and z components of each warp layer in turn
+    warp: Vec<Fbm>,
+    warp_strength: f32,
+    radius: f32,
+    elevation: (f32, f32),
+}
+
+impl TerrainSampler {
+    /// Distance from the planet center to the ground in the direction `dir`.
+    pub fn height(&self, dir: Vec3) -> f32 {
+        // on the unit sphere, the warp and the noise frequencies are independent of the radius
+        let p = self.warp(dir);
+        let n = self.noise.get([p.x() as f64, p.y() as f64, p.z() as f64]);
+        // spread the noise over the whole elevation range instead of clamping it, so the
+        // sea floor keeps its shape under the water
+        let t = ((n as f32 + 1.0) * 0.5).max(0.0).min(1.0);
+        self.radius * (self.elevation.0 + (self.elevation.1 - self.elevation.0) * t)
+    }
+
+    /// The direction the elevation noise is actually sampled in, on the unit sphere.
+    pub fn warp(&self, dir: Vec3) -> Vec3 {
+        let mut dir = dir.normalize();
+        if self.warp_strength == 0.0 {
+            return dir;
+        }
+        for layer in self.warp.chunks_exact(3) {
+            let p = [dir.x() as f64, dir.y() as f64, dir.z() as f64];
+            let offset = Vec3::new(
+                layer[0].get(p) as f32,
+                layer[1].get(p) as f32,
+                layer[2].get(p) as f32,
+            );
+            dir = (dir + offset * self.warp_strength).normalize();
+        }
+        dir
+    }
+}
+
+/// Mesh attribute holding each vertex's `Biome` as a `Uint`.
+pub const BIOME_ATTRIBUTE: &str = "Vertex_Biome";
+
+/// Vertex data only the planet shader reads, packed into its own buffer next to the usual
+/// `Vertex` one so asteroids, rings and the rest don't carry it. Meshes without the
+/// attributes get zeros. The tangents come from `Mesh::generate_tangents`.
+pub fn planet_vertex_layout() -> VertexBufferDescriptor {
+    VertexBufferDescriptor {
+        name: "PlanetVertex".into(),
+        stride: VertexFormat::Float4.get_size() + VertexFormat::Uint.get_size(),
+        step_mode: InputStepMode::Vertex,
+        attributes: vec![
+            VertexAttributeDescriptor {
+                name: "PlanetVertex_Tangent".into(),
+                offset: 0,
+                format: VertexFormat::Float4,
+                shader_location: 4,
+            },
+            VertexAttributeDescriptor {
+                name: "PlanetVertex_Biome".into(),
+                offset: VertexFormat::Float4.get_size(),
+                format: VertexFormat::Uint,
+                shader_location: 5,
+            },
+        ],
+    }
+}
+
+pub fn register_planet_vertex_layout(
+    mut vertex_buffer_descriptors: ResMut<VertexBufferDescriptors>,
+) {
+    vertex_buffer_descriptors.set(planet_vertex_layout());
+}
+
+/// Fills `BIOME_ATTRIBUTE` from the mesh's positions, `sea_level` is a radius.
+pub fn add_planet_attributes(mesh: &mut Mesh, sea_level: f32) {
+    let biomes = mesh
+        .attribute::<[f32; 3]>(VertexAttribute::POSITION)
+        .unwrap()
+        .iter()
+        .map(|p| Biome::classify(Vec3::from(*p).length() - sea_level) as u32)
+        .collect();
+    mesh.insert_attribute(VertexAttribute {
+        name: BIOME_ATTRIBUTE.into(),
+        values: VertexAttributeValues::Uint(biomes),
+    });
+}
+
+const HISTOGRAM_BINS: usize = 1024;
+
+/// The height that leaves `fraction` of `heights` above it, found with a histogram over
+/// `range`. Heights outside the range count towards the nearest end.
+pub fn level_for_fraction(heights: &[f32], fraction: f32, range: (f32, f32)) -> f32 {
+    let (low, high) = range;
+    if high <= low || !(high - low).is_finite() {
+        // nothing to spread the heights over, and the bins would all be zero wide
+        return low;
+    }
+    let bin_size = (high - low) / HISTOGRAM_BINS as f32;
+    let mut bins = vec![0usize; HISTOGRAM_BINS];
+    for height in heights {
+        let bin = ((height - low) / bin_size).max(0.0) as usize;
+        bins[bin.min(HISTOGRAM_BINS - 1)] += 1;
+    }
+
+    // walk down from the top until enough of the surface is above
+    let wanted = fraction.max(0.0).min(1.0) * heights.len() as f32;
+    let mut above = 0.0;
+    for (bin, count) in bins.iter().enumerate().rev() {
+        let count = *count as f32;
+        if above + count >= wanted {
+            // assume the heights are spread evenly inside the bin
+            let inside = if count > 0.0 {
+                (wanted - above) / count
+            } else {
+                0.0
+            };
+            return low + bin_size * (bin as f32 + 1.0 - inside);
+        }
+        above += count;
+    }
+    low
+}
+
+#[cfg(test)]
+mod tests {
+    use super::level_for_fraction;
+
+    fn fraction_above(heights: &[f32], level: f32) -> f32 {
+        heights.iter().filter(|h| **h > level).count() as f32 / heights.len() as f32
+    }
+
+    #[test]
+    fn test_level_for_fraction() {
+        // evenly spread
+        let heights = (0..1000).map(|i| i as f32 + 0.5).collect::<Vec<_>>();
+        for &fraction in &[0.0, 0.1, 0.35, 0.5, 0.9, 1.0] {
+            let level = level_for_fraction(&heights, fraction, (0.0, 1000.0));
+            assert!(
+                (fraction_above(&heights, level) - fraction).abs() <= 0.002,
+                "{} gave level {}",
+                fraction,
+                level
+            );
+        }
+
+        // bunched up low, with a few outside the range
+        let mut heights = (0..1000)
+            .map(|i| (i as f32 / 1000.0).powi(3) * 1000.0)
+            .collect::<Vec<_>>();
+        heights.extend(vec![-50.0; 10]);
+        heights.extend(vec![1200.0; 10]);
+        let level = level_for_fraction(&heights, 0.35, (0.0, 1000.0));
+        assert!((fraction_above(&heights, level) - 0.35).abs() <= 0.01);
+
+        // a flat planet has no range to search
+        assert_eq!(level_for_fraction(&heights, 0.35, (2.0, 2.0)), 2.0);
+    }
+}
@@ -0,0 +1,240 @@
+use crate::{
+    geo::direction_to_lat_long,
+    planet::{Planet, PlanetConfig},
+    wasd_camera::{CameraMarker, CursorListener},
+};
+use bevy::{
+    prelude::*,
+    render::{camera::Camera, mesh::VertexAttribute},
+};
+
+#[derive(Clone, Copy, Debug)]
+pub struct Ray {
+    pub origin: Vec3,
+    /// doesn't need to be normalized
+    pub direction: Vec3,
+}
+
+impl Ray {
+    /// `cursor` is in normalized window coordinates, (0, 0) bottom left and (1, 1) top right.
+    pub fn from_camera(camera: &Camera, camera_transform: &Transform, cursor: Vec2) -> Self {
+        let ndc = cursor * 2.0 - Vec2::one();
+        let inverse = (camera.projection_matrix * camera_transform.value.inverse()).inverse();
+        let near = inverse * Vec4::new(ndc.x(), ndc.y(), 0.0, 1.0);
+        let far = inverse * Vec4::new(ndc.x(), ndc.y(), 1.0, 1.0);
+        let near = near.truncate() / near.w();
+        let far = far.truncate() / far.w();
+        Self {
+            origin: near,
+            direction: (far - near).normalize(),
+        }
+    }
+
+    pub fn transformed(&self, matrix: &Mat4) -> Self {
+        Self {
+            origin: (*matrix * self.origin.extend(1.0)).truncate(),
+            direction: (*matrix * self.direction.extend(0.0)).truncate(),
+        }
+    }
+
+    pub fn at(&self, distance: f32) -> Vec3 {
+        self.origin + self.direction.normalize() * distance
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct RayHit {
+    /// distance along the ray in world units
+    pub distance: f32,
+    /// world position of the hit
+    pub point: Vec3,
+    /// which triangle of the mesh was hit, the vertex indices are `vertices`
+    pub triangle: usize,
+    pub vertices: [u32; 3],
+    /// radians, see `geo::direction_to_lat_long`
+    pub latitude: f32,
+    pub longitude: f32,
+}
+
+/// Ray-sphere intersection for a sphere centered at the origin, same as `rsi` in the planet
+/// shader. Returns the near and far distances in units of `direction`, `None` on a miss.
+pub fn ray_sphere(origin: Vec3, direction: Vec3, radius: f32) -> Option<(f32, f32)> {
+    let a = direction.dot(direction);
+    let b = 2.0 * direction.dot(origin);
+    let c = origin.dot(origin) - (radius * radius);
+    let d = (b * b) - 4.0 * a * c;
+    if d < 0.0 {
+        return None;
+    }
+    Some(((-b - d.sqrt()) / (2.0 * a), (-b + d.sqrt()) / (2.0 * a)))
+}
+
+// Moller-Trumbore, only front or back doesn't matter here
+fn ray_triangle(origin: Vec3, direction: Vec3, a: Vec3, b: Vec3, c: Vec3) -> Option<f32> {
+    let edge1 = b - a;
+    let edge2 = c - a;
+    let p = direction.cross(edge2);
+    let det = edge1.dot(p);
+    if det.abs() < std::f32::EPSILON {
+        return None;
+    }
+    let inv_det = 1.0 / det;
+    let to_origin = origin - a;
+    let u = to_origin.dot(p) * inv_det;
+    if u < 0.0 || u > 1.0 {
+        return None;
+    }
+    let q = to_origin.cross(edge1);
+    let v = direction.dot(q) * inv_det;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+    Some(edge2.dot(q) * inv_det)
+}
+
+/// Casts a world space ray against a planet mesh. The bounding sphere is tested first, then
+/// the closest displaced triangle is found inside it.
+pub fn raycast_planet(
+    ray: &Ray,
+    planet_transform: &Transform,
+    mesh: &Mesh,
+    bounding_radius: f32,
+) -> Option<RayHit> {
+    let local = ray.transformed(&planet_transform.value.inverse());
+    let direction = local.direction.normalize();
+
+    let (near, far) = ray_sphere(local.origin, direction, bounding_radius)?;
+    if far < 0.0 {
+        return None;
+    }
+    let near = near.max(0.0);
+
+    let positions = mesh.attribute::<[f32; 3]>(VertexAttribute::POSITION)?;
+    let indices = mesh.triangle_indices();
+
+    let mut closest: Option<(f32, usize)> = None;
+    for (triangle, tri) in indices.chunks_exact(3).enumerate() {
+        let a = Vec3::from(positions[tri[0] as usize]);
+        let b = Vec3::from(positions[tri[1] as usize]);
+        let c = Vec3::from(positions[tri[2] as usize]);
+        if let Some(t) = ray_triangle(local.origin, direction, a, b, c) {
+            if t >= near && t <= far && closest.map_or(true, |(best, _)| t < best) {
+                closest = Some((t, triangle));
+            }
+        }
+    }
+
+    let (t, triangle) = closest?;
+    let local_point = local.origin + direction * t;
+    let point = (planet_transform.value * local_point.extend(1.0)).truncate();
+    let (latitude, longitude) = direction_to_lat_long(local_point);
+    let tri = &indices[triangle * 3..triangle * 3 + 3];
+    Some(RayHit {
+        distance: (point - ray.origin).length(),
+        point,
+        triangle,
+        vertices: [tri[0], tri[1], tri[2]],
+        latitude,
+        longitude,
+    })
+}
+
+#[derive(Default)]
+pub struct PlanetPick {
+    pub hit: Option<RayHit>,
+}
+
+// left click picks a point on the planet under the cursor
+pub fn pick_planet(
+    mouse: Res<Input<MouseButton>>,
+    cursor: Res<CursorListener>,
+    config: Res<PlanetConfig>,
+    meshes: Res<Assets<Mesh>>,
+    mut pick: ResMut<PlanetPick>,
+    mut camera_query: Query<(&Camera, &Transform, &CameraMarker)>,
+    mut planet_query: Query<(&Transform, &Handle<Mesh>, &Planet)>,
+) {
+    if !mouse.just_pressed(MouseButton::Left) {
+        return;
+    }
+    for (camera, camera_transform, _cam) in &mut camera_query.iter() {
+        let ray = Ray::from_camera(camera, camera_transform, cursor.pos);
+        pick.hit = None;
+        for (transform, handle, _planet) in &mut planet_query.iter() {
+            if let Some(mesh) = meshes.get(&handle) {
+                let bounding_radius = config.radius * config.elevation.1;
+                if let Some(hit) = raycast_planet(&ray, transform, mesh, bounding_radius) {
+                    if pick.hit.map_or(true, |best| hit.distance < best.distance) {
+                        pick.hit = Some(hit);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ray_sphere, raycast_planet, Ray};
+    use bevy::{prelude::*, render::mesh::shape};
+
+    fn unit_icosphere() -> Mesh {
+        Mesh::from(shape::Icosphere {
+            radius: 1.0,
+            subdivisions: 3,
+            split_seam: false,
+        })
+    }
+
+    #[test]
+    fn test_ray_sphere() {
+        let origin = Vec3::new(0.0, 0.0, 5.0);
+        let direction = Vec3::new(0.0, 0.0, -1.0);
+        let (near, far) = ray_sphere(origin, direction, 1.0).unwrap();
+        assert!((near - 4.0).abs() < 1e-5);
+        assert!((far - 6.0).abs() < 1e-5);
+
+        assert!(ray_sphere(Vec3::new(0.0, 1.5, 5.0), direction, 1.0).is_none());
+    }
+
+    #[test]
+    fn test_raycast_hit() {
+        let mesh = unit_icosphere();
+        let transform = Transform::new(Mat4::from_translation(Vec3::new(10.0, 0.0, 0.0)));
+        let ray = Ray {
+            origin: Vec3::new(10.0, 0.0, 5.0),
+            direction: Vec3::new(0.0, 0.0, -2.0),
+        };
+        let hit = raycast_planet(&ray, &transform, &mesh, 1.0).unwrap();
+
+        // the flat triangles sit just inside the sphere, and the near side wins
+        assert!(
+            hit.distance >= 4.0 && hit.distance < 4.05,
+            "{}",
+            hit.distance
+        );
+        assert!((hit.point - ray.at(hit.distance)).length() < 1e-4);
+        assert!(hit.latitude.abs() < 1e-4);
+        assert!((hit.longitude + std::f32::consts::FRAC_PI_2).abs() < 1e-4);
+
+        let indices = mesh.triangle_indices();
+        assert_eq!(
+            &indices[hit.triangle * 3..hit.triangle * 3 + 3],
+            &hit.vertices[..]
+        );
+    }
+
+    #[test]
+    fn test_raycast_miss() {
+        let mesh = unit_icosphere();
+        let transform = Transform::new(Mat4::identity());
+        let ray = Ray {
+            origin: Vec3::new(0.0, 1.5, 5.0),
+            direction: Vec3::new(0.0, 0.0, -1.0),
+        };
+        // rejected by the bounding sphere
+        assert!(raycast_planet(&ray, &transform, &mesh, 1.0).is_none());
+        // inside a loose bounding sphere, but no triangle is hit
+        assert!(raycast_planet(&ray, &transform, &mesh, 2.0).is_none());
+    }
+}
@@ -0,0 +1,131 @@
+use crate::shaders::glsl;
+use bevy::{
+    math::vec2,
+    prelude::*,
+    render::{
+        pipeline::{
+            CullMode, DynamicBinding, PipelineDescriptor, PipelineSpecialization, RenderPipeline,
+        },
+        render_graph::{base, AssetRenderResourcesNode, RenderGraph},
+        renderer::RenderResources,
+        shader::{ShaderStage, ShaderStages},
+        texture::TextureFormat,
+    },
+};
+use noise::*;
+
+pub struct RingConfig {
+    pub inner_radius: f32,
+    pub outer_radius: f32,
+    pub segments: usize,
+    pub seed: u32,
+    pub color: Color,
+    /// number of samples in the radial texture
+    pub resolution: usize,
+}
+
+impl Default for RingConfig {
+    fn default() -> Self {
+        Self {
+            inner_radius: 65000.0,
+            outer_radius: 110000.0,
+            segments: 128,
+            seed: 0,
+            color: Color::rgb(0.85, 0.75, 0.6),
+            resolution: 512,
+        }
+    }
+}
+
+#[derive(RenderResources)]
+pub struct RingMaterial {
+    pub texture: Handle<Texture>,
+    pub planet_radius: f32,
+    pub sun_position: Vec4,
+}
+
+/// Bakes the radial density (alpha) and colour of the rings into a 1 pixel tall texture.
+pub fn ring_texture(config: &RingConfig) -> Texture {
+    let bands = Fbm::new()
+        .set_seed(config.seed)
+        .set_octaves(5)
+        .set_frequency(24.0);
+    let tint = Fbm::new()
+        .set_seed(config.seed.wrapping_add(1))
+        .set_octaves(2)
+        .set_frequency(6.0);
+
+    let mut data = Vec::with_capacity(config.resolution * 4);
+    for i in 0..config.resolution {
+        let x = i as f64 / config.resolution as f64;
+        let density = (bands.get([x, 0.5]) as f32 * 0.5 + 0.5).max(0.0).min(1.0);
+        // fade out towards both edges so the rings don't end in a hard line
+        let edge = (x.min(1.0 - x) as f32 * 20.0).min(1.0);
+        let shade = 0.8 + 0.4 * tint.get([x, 0.5]) as f32;
+        data.push(((config.color.r * shade).min(1.0) * 255.0) as u8);
+        data.push(((config.color.g * shade).min(1.0) * 255.0) as u8);
+        data.push(((config.color.b * shade).min(1.0) * 255.0) as u8);
+        data.push((density * edge * 255.0) as u8);
+    }
+
+    Texture::new(
+        vec2(config.resolution as f32, 1.0),
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+    )
+}
+
+/// Sets up the ring shaders and render graph node, returning the pipelines to put on ring entities.
+pub fn ring_pipelines(
+    pipelines: &mut Assets<PipelineDescriptor>,
+    shaders: &mut Assets<Shader>,
+    render_graph: &mut RenderGraph,
+) -> RenderPipelines {
+    let mut descriptor = PipelineDescriptor::default_config(ShaderStages {
+        vertex: shaders.add(Shader::from_glsl(
+            ShaderStage::Vertex,
+            include_str!("../assets/ring_shader.vert"),
+        )),
+        fragment: Some(shaders.add(Shader::from_glsl(
+            ShaderStage::Fragment,
+            &glsl(include_str!("../assets/ring_shader.frag")),
+        ))),
+    });
+    // rings are seen from above and below
+    if let Some(ref mut rasterization_state) = descriptor.rasterization_state {
+        rasterization_state.cull_mode = CullMode::None;
+    }
+    let pipeline_handle = pipelines.add(descriptor);
+
+    render_graph.add_system_node(
+        "ring_material",
+        AssetRenderResourcesNode::<RingMaterial>::new(true),
+    );
+    render_graph
+        .add_node_edge("ring_material", base::node::MAIN_PASS)
+        .unwrap();
+
+    RenderPipelines::from_pipelines(vec![RenderPipeline::specialized(
+        pipeline_handle,
+        PipelineSpecialization {
+            dynamic_bindings: vec![
+                // Transform
+                DynamicBinding {
+                    bind_group: 1,
+                    binding: 0,
+                },
+                // RingMaterial_planet_radius
+                DynamicBinding {
+                    bind_group: 1,
+                    binding: 3,
+                },
+                // RingMaterial_sun_position
+                DynamicBinding {
+                    bind_group: 1,
+                    binding: 4,
+                },
+            ],
+            ..Default::default()
+        },
+    )])
+}
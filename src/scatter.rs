@@ -0,0 +1,484 @@
+use crate::{
+    culling::InstanceBounds,
+    planet::{Planet, PlanetConfig},
+    rng::Rng,
+    surface::{Biome, PlanetSurface},
+    wasd_camera::CameraMarker,
+};
+use bevy::{
+    core::AsBytes,
+    math::vec3,
+    prelude::*,
+    render::{
+        mesh::{shape, Bounds, GenerateTangentsError},
+        pipeline::{
+            InputStepMode, VertexAttributeDescriptor, VertexBufferDescriptor,
+            VertexBufferDescriptors, VertexFormat,
+        },
+        renderer::{BufferId, BufferInfo, BufferUsage, RenderResourceContext},
+    },
+};
+use std::collections::HashMap;
+
+/// A mesh ready to be stamped onto the surface, +Y is up and the origin sits on the ground.
+/// Every object of a rule draws the same asset, added the first time a cell needs it.
+pub struct Template {
+    mesh: Mesh,
+    handle: Option<Handle<Mesh>>,
+}
+
+impl Template {
+    /// The mesh needs uvs for its tangents, templates share the planet's material and its
+    /// normal map.
+    pub fn new(mut mesh: Mesh, shaping: Mat4) -> Result<Self, GenerateTangentsError> {
+        // white, the instance colour tints it
+        mesh.set_color(Color::WHITE);
+        mesh.transform(&shaping);
+        mesh.generate_tangents()?;
+        Ok(Template { mesh, handle: None })
+    }
+}
+
+pub struct ScatterRule {
+    pub template: Template,
+    pub color: Color,
+    pub biome: Biome,
+    /// allowed height above sea level
+    pub elevation: (f32, f32),
+    /// steepest ground allowed, radians between the surface normal and straight up
+    pub max_slope: f32,
+    /// minimum distance between two objects of this rule
+    pub spacing: f32,
+    pub scale: (f32, f32),
+}
+
+impl ScatterRule {
+    pub fn defaults() -> Vec<ScatterRule> {
+        vec![
+            // trees
+            ScatterRule {
+                template: Template::new(
+                    Mesh::from(shape::Icosphere {
+                        radius: 1.0,
+                        subdivisions: 1,
+                        split_seam: false,
+                    }),
+                    Mat4::from_scale_rotation_translation(
+                        vec3(0.4, 1.5, 0.4),
+                        Quat::identity(),
+                        vec3(0.0, 1.5, 0.0),
+                    ),
+                )
+                .unwrap(),
+                color: Color::rgb(0.05, 0.3, 0.05),
+                biome: Biome::Land,
+                elevation: (200.0, 3500.0),
+                max_slope: 0.6,
+                spacing: 600.0,
+                scale: (30.0, 60.0),
+            },
+            // rocks
+            ScatterRule {
+                template: Template::new(
+                    Mesh::from(shape::Icosphere {
+                        radius: 1.0,
+                        subdivisions: 0,
+                        split_seam: false,
+                    }),
+                    Mat4::from_scale(vec3(1.0, 0.6, 0.8)),
+                )
+                .unwrap(),
+                color: Color::rgb(0.4, 0.38, 0.35),
+                biome: Biome::Land,
+                elevation: (1000.0, 5000.0),
+                max_slope: 1.2,
+                spacing: 900.0,
+                scale: (20.0, 50.0),
+            },
+            // buildings
+            ScatterRule {
+                template: Template::new(
+                    Mesh::from(shape::Cube { size: 1.0 }),
+                    Mat4::from_translation(vec3(0.0, 1.0, 0.0)),
+                )
+                .unwrap(),
+                color: Color::rgb(0.8, 0.8, 0.75),
+                biome: Biome::Land,
+                elevation: (0.0, 800.0),
+                max_slope: 0.2,
+                spacing: 1500.0,
+                scale: (30.0, 80.0),
+            },
+        ]
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Placement {
+    pub rule: usize,
+    pub transform: Mat4,
+}
+
+#[derive(Default)]
+pub struct ScatterCell {
+    pub center: Vec3,
+    pub placements: Vec<Placement>,
+    entities: Vec<Entity>,
+    instance_buffers: Vec<BufferId>,
+}
+
+/// The placements of one rule in a cell, ready for the `ScatterInstance` buffer.
+pub struct Instances {
+    pub rule: usize,
+    /// `scatter_instance_layout` elements, five `Float4`s each
+    pub data: Vec<[f32; 4]>,
+    /// around every instance, in planet space
+    pub bounds: Option<Bounds>,
+}
+
+/// Everything placed on the planet, grouped into cells for streaming. A loaded cell draws each
+/// rule's template once per placement with instancing, see `Scatter::cell_instances`. Positions
+/// are in planet space like `PlanetSurface`.
+pub struct Scatter {
+    pub rules: Vec<ScatterRule>,
+    pub cells: HashMap<(i32, i32, i32), ScatterCell>,
+    cell_size: f32,
+    stream_radius: f32,
+}
+
+/// The pipelines and material the instanced templates are drawn with. The pipelines need a
+/// vertex shader that reads the `ScatterInstance` buffer.
+pub struct ScatterRenderer<M: 'static + Send + Sync> {
+    pub render_pipelines: RenderPipelines,
+    pub material: Handle<M>,
+}
+
+impl Scatter {
+    pub fn new(config: &PlanetConfig, surface: &PlanetSurface, rules: Vec<ScatterRule>) -> Self {
+        let mut rng = Rng::new(config.scatter.seed);
+        let radius = config.sea_level_radius();
+        let cell_size = config.scatter.cell_size;
+        let mut cells = HashMap::<(i32, i32, i32), ScatterCell>::new();
+
+        for (index, rule) in rules.iter().enumerate() {
+            for dir in poisson_disk(&mut rng, radius, rule.spacing) {
+                let sample = surface.sample(dir);
+                let slope = sample.normal.dot(dir).max(-1.0).min(1.0).acos();
+                if sample.biome != rule.biome
+                    || sample.elevation < rule.elevation.0
+                    || sample.elevation > rule.elevation.1
+                    || slope > rule.max_slope
+                {
+                    continue;
+                }
+
+                let up = rotation_to(sample.normal);
+                let yaw = Quat::from_rotation_y(rng.range(0.0, std::f32::consts::PI * 2.0));
+                let scale = rng.range(rule.scale.0, rule.scale.1);
+                let transform = Mat4::from_scale_rotation_translation(
+                    Vec3::splat(scale),
+                    up * yaw,
+                    sample.point,
+                );
+
+                let key = cell_key(sample.point, cell_size);
+                let cell = cells.entry(key).or_insert_with(|| ScatterCell {
+                    center: vec3(key.0 as f32 + 0.5, key.1 as f32 + 0.5, key.2 as f32 + 0.5)
+                        * cell_size,
+                    ..Default::default()
+                });
+                cell.placements.push(Placement {
+                    rule: index,
+                    transform,
+                });
+            }
+        }
+
+        Self {
+            rules,
+            cells,
+            cell_size,
+            stream_radius: config.scatter.stream_radius,
+        }
+    }
+
+    /// Groups a cell's placements by rule, one draw per rule and cell. Only the transforms and
+    /// colours go to the GPU, the template's vertices are shared by every instance.
+    pub fn cell_instances(&self, cell: &ScatterCell) -> Vec<Instances> {
+        let mut instances = (0..self.rules.len())
+            .map(|rule| Instances {
+                rule,
+                data: Vec::new(),
+                bounds: None,
+            })
+            .collect::<Vec<_>>();
+        let mut bounds = vec![Vec::new(); self.rules.len()];
+        for placement in cell.placements.iter() {
+            let rule = &self.rules[placement.rule];
+            let transform = &placement.transform;
+            let data = &mut instances[placement.rule].data;
+            data.push(transform.x_axis().into());
+            data.push(transform.y_axis().into());
+            data.push(transform.z_axis().into());
+            data.push(transform.w_axis().into());
+            data.push([rule.color.r, rule.color.g, rule.color.b, rule.color.a]);
+            if let Some(template_bounds) = rule.template.mesh.bounds() {
+                bounds[placement.rule].push(Bounds {
+                    aabb: template_bounds.aabb.transformed(transform),
+                    sphere: template_bounds.sphere.transformed(transform),
+                });
+            }
+        }
+        for (instances, bounds) in instances.iter_mut().zip(bounds) {
+            instances.bounds = Bounds::enclosing(&bounds);
+        }
+        instances.retain(|instances| !instances.data.is_empty());
+        instances
+    }
+
+    fn template_handle(&mut self, rule: usize, meshes: &mut Assets<Mesh>) -> Handle<Mesh> {
+        let template = &mut self.rules[rule].template;
+        let mesh = &template.mesh;
+        *template
+            .handle
+            .get_or_insert_with(|| meshes.add(mesh.clone()))
+    }
+}
+
+/// The templates' tangents, next to the usual `Vertex` buffer.
+pub fn scatter_vertex_layout() -> VertexBufferDescriptor {
+    VertexBufferDescriptor {
+        name: "ScatterVertex".into(),
+        stride: VertexFormat::Float4.get_size(),
+        step_mode: InputStepMode::Vertex,
+        attributes: vec![VertexAttributeDescriptor {
+            name: "ScatterVertex_Tangent".into(),
+            offset: 0,
+            format: VertexFormat::Float4,
+            shader_location: 4,
+        }],
+    }
+}
+
+/// The per-instance buffer of scatter entities: the model matrix in planet space one column at
+/// a time, then the colour.
+pub fn scatter_instance_layout() -> VertexBufferDescriptor {
+    let attribute = |name: &str, index: u32| VertexAttributeDescriptor {
+        name: format!("I_ScatterInstance_{}", name).into(),
+        offset: index as u64 * VertexFormat::Float4.get_size(),
+        format: VertexFormat::Float4,
+        shader_location: 5 + index,
+    };
+    VertexBufferDescriptor {
+        name: "ScatterInstance".into(),
+        stride: 5 * VertexFormat::Float4.get_size(),
+        step_mode: InputStepMode::Instance,
+        attributes: vec![
+            attribute("Model0", 0),
+            attribute("Model1", 1),
+            attribute("Model2", 2),
+            attribute("Model3", 3),
+            attribute("Color", 4),
+        ],
+    }
+}
+
+pub fn register_scatter_layouts(mut vertex_buffer_descriptors: ResMut<VertexBufferDescriptors>) {
+    vertex_buffer_descriptors.set(scatter_vertex_layout());
+    vertex_buffer_descriptors.set(scatter_instance_layout());
+}
+
+fn cell_key(point: Vec3, cell_size: f32) -> (i32, i32, i32) {
+    (
+        (point.x() / cell_size).floor() as i32,
+        (point.y() / cell_size).floor() as i32,
+        (point.z() / cell_size).floor() as i32,
+    )
+}
+
+// shortest rotation taking +Y to `up`
+fn rotation_to(up: Vec3) -> Quat {
+    let axis = Vec3::unit_y().cross(up);
+    if axis.length() < 1e-6 {
+        return if up.y() > 0.0 {
+            Quat::identity()
+        } else {
+            Quat::from_rotation_x(std::f32::consts::PI)
+        };
+    }
+    Quat::from_axis_angle(
+        axis.normalize(),
+        Vec3::unit_y().dot(up).max(-1.0).min(1.0).acos(),
+    )
+}
+
+/// Dart throwing on a sphere of `radius`: candidates closer than `spacing` to an accepted point
+/// are thrown away. Returns unit directions.
+pub fn poisson_disk(rng: &mut Rng, radius: f32, spacing: f32) -> Vec<Vec3> {
+    let area = 4.0 * std::f32::consts::PI * radius * radius;
+    let attempts = (area / (spacing * spacing)) as usize * 4;
+    let mut grid = HashMap::<(i32, i32, i32), Vec<Vec3>>::new();
+    let mut accepted = Vec::new();
+
+    for _ in 0..attempts {
+        let dir = rng.unit_vector();
+        let point = dir * radius;
+        let key = cell_key(point, spacing);
+
+        let mut free = true;
+        'neighbours: for x in -1..=1 {
+            for y in -1..=1 {
+                for z in -1..=1 {
+                    if let Some(points) = grid.get(&(key.0 + x, key.1 + y, key.2 + z)) {
+                        if points.iter().any(|p| (*p - point).length() < spacing) {
+                            free = false;
+                            break 'neighbours;
+                        }
+                    }
+                }
+            }
+        }
+        if free {
+            grid.entry(key).or_insert_with(Vec::new).push(point);
+            accepted.push(dir);
+        }
+    }
+    accepted
+}
+
+// spawns the cells near the camera as children of the planet, one instanced entity per rule,
+// and drops the ones that fell behind
+pub fn stream_scatter<M: 'static + Send + Sync>(
+    mut commands: Commands,
+    mut scatter: ResMut<Scatter>,
+    renderer: Res<ScatterRenderer<M>>,
+    render_resource_context: Res<Box<dyn RenderResourceContext>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut camera_query: Query<(&Transform, &CameraMarker)>,
+    mut planet_query: Query<(Entity, &Transform, &Planet)>,
+    mut children_query: Query<&mut Children>,
+) {
+    for (camera_transform, _cam) in &mut camera_query.iter() {
+        for (planet, planet_transform, _planet) in &mut planet_query.iter() {
+            let camera =
+                (planet_transform.value.inverse() * camera_transform.value.w_axis()).truncate();
+            let spawn_radius = scatter.stream_radius;
+            // a little slack so cells on the border don't flicker in and out
+            let despawn_radius = spawn_radius + scatter.cell_size;
+
+            let keys = scatter.cells.keys().copied().collect::<Vec<_>>();
+            for key in keys {
+                let (distance, loaded) = {
+                    let cell = &scatter.cells[&key];
+                    ((cell.center - camera).length(), !cell.entities.is_empty())
+                };
+                if distance < spawn_radius && !loaded {
+                    for instances in scatter.cell_instances(&scatter.cells[&key]) {
+                        let mesh = scatter.template_handle(instances.rule, &mut meshes);
+                        let instance_buffer = render_resource_context.create_buffer_with_data(
+                            BufferInfo {
+                                buffer_usage: BufferUsage::VERTEX,
+                                ..Default::default()
+                            },
+                            instances.data.as_bytes(),
+                        );
+                        let mut render_pipelines = renderer.render_pipelines.clone();
+                        render_pipelines.bindings.set_vertex_buffer(
+                            "ScatterInstance",
+                            instance_buffer,
+                            None,
+                        );
+                        commands
+                            .spawn(MeshComponents {
+                                mesh,
+                                render_pipelines,
+                                ..Default::default()
+                            })
+                            .with(renderer.material);
+                        if let Some(bounds) = instances.bounds {
+                            commands.with(InstanceBounds(bounds));
+                        }
+                        let entity = commands.current_entity().unwrap();
+                        commands.push_children(planet, &[entity]);
+                        let cell = scatter.cells.get_mut(&key).unwrap();
+                        cell.entities.push(entity);
+                        cell.instance_buffers.push(instance_buffer);
+                    }
+                } else if distance > despawn_radius && loaded {
+                    let cell = scatter.cells.get_mut(&key).unwrap();
+                    // despawning doesn't touch the parent, the planet would keep dead children
+                    if let Ok(mut children) = children_query.get_mut::<Children>(planet) {
+                        children.0.retain(|child| !cell.entities.contains(child));
+                    }
+                    for entity in cell.entities.drain(..) {
+                        commands.despawn(entity);
+                    }
+                    for instance_buffer in cell.instance_buffers.drain(..) {
+                        render_resource_context.remove_buffer(instance_buffer);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{scatter_instance_layout, Placement, Scatter, ScatterCell, ScatterRule};
+    use bevy::{math::vec3, prelude::*, render::mesh::VertexAttribute};
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_templates_have_tangents() {
+        // the normal map path of the shared material needs them
+        for rule in ScatterRule::defaults() {
+            let tangents = rule
+                .template
+                .mesh
+                .attribute::<[f32; 4]>(VertexAttribute::TANGENT)
+                .unwrap();
+            assert_eq!(tangents.len(), rule.template.mesh.vertex_count());
+        }
+    }
+
+    #[test]
+    fn test_cell_instances() {
+        let scatter = Scatter {
+            rules: ScatterRule::defaults(),
+            cells: HashMap::new(),
+            cell_size: 1000.0,
+            stream_radius: 1000.0,
+        };
+        let placement = |rule: usize, x: f32| Placement {
+            rule,
+            transform: Mat4::from_scale_rotation_translation(
+                Vec3::splat(2.0),
+                Quat::identity(),
+                vec3(x, 0.0, 0.0),
+            ),
+        };
+        let cell = ScatterCell {
+            placements: vec![placement(2, 10.0), placement(0, 20.0), placement(2, 30.0)],
+            ..Default::default()
+        };
+
+        let instances = scatter.cell_instances(&cell);
+        assert_eq!(instances.len(), 2, "rules without placements are left out");
+        assert_eq!(instances[0].rule, 0);
+        assert_eq!(instances[1].rule, 2);
+
+        let stride = scatter_instance_layout().stride as usize;
+        let buildings = &instances[1];
+        assert_eq!(buildings.data.len() * 16, 2 * stride);
+        // translations are the last column of each matrix, in placement order
+        assert_eq!(buildings.data[3], [10.0, 0.0, 0.0, 1.0]);
+        assert_eq!(buildings.data[8], [30.0, 0.0, 0.0, 1.0]);
+        let color = scatter.rules[2].color;
+        assert_eq!(buildings.data[4], [color.r, color.g, color.b, color.a]);
+
+        // the cube template spans -1..1 and sits on the ground, doubled and moved along x
+        let bounds = buildings.bounds.unwrap();
+        assert_eq!(bounds.aabb.min, vec3(8.0, 0.0, -2.0));
+        assert_eq!(bounds.aabb.max, vec3(32.0, 4.0, 2.0));
+    }
+}
@@ -0,0 +1,46 @@
+/// GLSL shared between shaders, pulled in with `#include "<name>"`.
+const INCLUDES: &[(&str, &str)] = &[
+    ("noise.glsl", include_str!("../assets/noise.glsl")),
+    ("rsi.glsl", include_str!("../assets/rsi.glsl")),
+];
+
+/// Replaces every `#include "<name>"` line in `source` with the shared file of that name.
+/// shaderc is only ever handed a single string, so the includes have to be resolved here.
+pub fn glsl(source: &str) -> String {
+    let mut out = String::with_capacity(source.len());
+    for line in source.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with("#include") {
+            let name = trimmed["#include".len()..].trim().trim_matches('"');
+            let (_, include) = INCLUDES
+                .iter()
+                .find(|(include_name, _)| *include_name == name)
+                .unwrap_or_else(|| panic!("unknown shader include {:?}", name));
+            out.push_str(include);
+        } else {
+            out.push_str(line);
+        }
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::glsl;
+
+    #[test]
+    fn test_includes() {
+        let source = glsl("#version 450\n#include \"noise.glsl\"\nvoid main() {}\n");
+        assert!(source.starts_with("#version 450\n"));
+        assert!(source.contains("float snoise(vec3 v)"));
+        assert!(source.ends_with("void main() {}\n"));
+        assert!(!source.contains("#include"));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_unknown_include() {
+        glsl("#include \"missing.glsl\"\n");
+    }
+}
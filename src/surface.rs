@@ -0,0 +1,106 @@
+use crate::{
+    geo::{lat_long_to_direction, LocalFrame},
+    planet::{PlanetConfig, TerrainSampler},
+};
+use bevy::prelude::*;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Biome {
+    Ocean,
+    Land,
+}
+
+impl Biome {
+    /// `elevation` is measured from sea level, negative is underwater.
+    pub fn classify(elevation: f32) -> Self {
+        if elevation > 0.0 {
+            Biome::Land
+        } else {
+            Biome::Ocean
+        }
+    }
+
+    /// The vertex colour the planet mesh uses for this biome.
+    pub fn color(&self) -> Color {
+        match self {
+            Biome::Land => Color::rgb(0.0, 0.5, 0.0),
+            // sea floor, the ocean mesh does the water
+            Biome::Ocean => Color::rgb(0.35, 0.3, 0.2),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct SurfaceSample {
+    /// unit vector from the planet center
+    pub direction: Vec3,
+    /// height above sea level
+    pub elevation: f32,
+    /// point on the ground, relative to the planet center
+    pub point: Vec3,
+    pub normal: Vec3,
+    pub biome: Biome,
+    pub color: Color,
+}
+
+/// CPU side answers to "where is the ground", using the same height function the planet
+/// mesh is built from. Everything is in planet space: untransformed, centered on the origin.
+pub struct PlanetSurface {
+    terrain: TerrainSampler,
+    sea_level: f32,
+    /// angular step used for finite difference normals, roughly one mesh edge
+    normal_step: f32,
+}
+
+impl PlanetSurface {
+    pub fn new(config: &PlanetConfig) -> Self {
+        Self {
+            terrain: config.terrain(),
+            sea_level: config.sea_level_radius(),
+            normal_step: 1.1 / (config.subdivisions as f32 + 1.0),
+        }
+    }
+
+    /// Distance from the planet center to the ground.
+    pub fn height(&self, direction: Vec3) -> f32 {
+        self.terrain.height(direction)
+    }
+
+    pub fn elevation(&self, direction: Vec3) -> f32 {
+        self.height(direction) - self.sea_level
+    }
+
+    pub fn point(&self, direction: Vec3) -> Vec3 {
+        let direction = direction.normalize();
+        direction * self.height(direction)
+    }
+
+    pub fn normal(&self, direction: Vec3) -> Vec3 {
+        let frame = LocalFrame::at(direction);
+        let center = self.point(frame.up);
+        let a = self.point(frame.up + frame.east * self.normal_step);
+        let b = self.point(frame.up + frame.north * self.normal_step);
+        // east x north points away from the center, so this does too
+        (a - center).cross(b - center).normalize()
+    }
+
+    pub fn sample(&self, direction: Vec3) -> SurfaceSample {
+        let direction = direction.normalize();
+        let height = self.height(direction);
+        let elevation = height - self.sea_level;
+        let biome = Biome::classify(elevation);
+        SurfaceSample {
+            direction,
+            elevation,
+            point: direction * height,
+            normal: self.normal(direction),
+            biome,
+            color: biome.color(),
+        }
+    }
+
+    /// Latitude and longitude are in radians, see `geo::lat_long_to_direction`.
+    pub fn sample_lat_long(&self, latitude: f32, longitude: f32) -> SurfaceSample {
+        self.sample(lat_long_to_direction(latitude, longitude))
+    }
+}
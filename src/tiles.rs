@@ -0,0 +1,234 @@
+use crate::geo::direction_to_lat_long;
+use bevy::{
+    prelude::*,
+    render::{
+        mesh::{shape, VertexAttribute},
+        pipeline::PrimitiveTopology,
+    },
+};
+use std::collections::HashMap;
+
+/// One cell of the tile grid. Twelve of them are pentagons, the rest are hexagons.
+pub struct Tile {
+    /// unit vector to the middle of the tile, this is the icosphere vertex it was built from
+    pub center: Vec3,
+    /// indices into `HexGrid::corners`, counter-clockwise seen from outside
+    pub corners: Vec<usize>,
+    /// `neighbours[i]` shares the edge between `corners[i]` and `corners[i + 1]`
+    pub neighbours: Vec<usize>,
+    pub color: Color,
+}
+
+/// A Goldberg polyhedron: the dual of a subdivided icosahedron, with one tile per icosphere
+/// vertex and one corner per icosphere triangle. Tile ids are the icosphere's vertex indices.
+pub struct HexGrid {
+    pub tiles: Vec<Tile>,
+    /// unit vectors, the middle of the icosphere triangle each corner comes from
+    pub corners: Vec<Vec3>,
+    /// the three tiles meeting at each corner
+    corner_tiles: Vec<[usize; 3]>,
+}
+
+impl HexGrid {
+    pub fn new(subdivisions: usize) -> Self {
+        let sphere = Mesh::from(shape::Icosphere {
+            radius: 1.0,
+            subdivisions,
+            split_seam: false,
+        });
+        let centers = sphere
+            .attribute::<[f32; 3]>(VertexAttribute::POSITION)
+            .unwrap()
+            .iter()
+            .map(|p| Vec3::from(*p).normalize())
+            .collect::<Vec<_>>();
+        let indices = sphere.indices.unwrap_or_default();
+
+        let mut corners = Vec::with_capacity(indices.len() / 3);
+        let mut corner_tiles = Vec::with_capacity(indices.len() / 3);
+        // for every tile, the corner of each triangle around it keyed by the vertex
+        // that follows the tile in that triangle
+        let mut fans = vec![HashMap::new(); centers.len()];
+        for (corner, tri) in indices.chunks_exact(3).enumerate() {
+            let (a, b, c) = (tri[0] as usize, tri[1] as usize, tri[2] as usize);
+            corners.push((centers[a] + centers[b] + centers[c]).normalize());
+            corner_tiles.push([a, b, c]);
+            fans[a].insert(b, (corner, c));
+            fans[b].insert(c, (corner, a));
+            fans[c].insert(a, (corner, b));
+        }
+
+        let tiles = centers
+            .iter()
+            .zip(fans.iter())
+            .map(|(center, fan)| {
+                // walk around the tile, each triangle hands over to the one sharing its far edge
+                let mut tile_corners = Vec::with_capacity(fan.len());
+                let mut neighbours = Vec::with_capacity(fan.len());
+                if let Some(&start) = fan.keys().next() {
+                    let mut current = start;
+                    loop {
+                        let (corner, following) = fan[&current];
+                        tile_corners.push(corner);
+                        // the edge after this corner crosses the icosphere edge to `following`
+                        neighbours.push(following);
+                        current = following;
+                        if current == start || tile_corners.len() == fan.len() {
+                            break;
+                        }
+                    }
+                }
+                Tile {
+                    center: *center,
+                    corners: tile_corners,
+                    neighbours,
+                    color: Color::rgb(1.0, 1.0, 1.0),
+                }
+            })
+            .collect();
+
+        Self {
+            tiles,
+            corners,
+            corner_tiles,
+        }
+    }
+
+    pub fn neighbours(&self, tile: usize) -> &[usize] {
+        &self.tiles[tile].neighbours
+    }
+
+    /// The tile containing `direction`. Walks towards it from `start`, pass the last answer
+    /// when asking about nearby points and it only takes a few steps.
+    pub fn tile_at_from(&self, direction: Vec3, start: usize) -> usize {
+        let direction = direction.normalize();
+        let mut tile = start;
+        let mut best = self.tiles[tile].center.dot(direction);
+        // the icosphere is close enough to a delaunay triangulation that greedy steps
+        // always end on the nearest center, which is the tile containing the point
+        loop {
+            let closer = self.tiles[tile]
+                .neighbours
+                .iter()
+                .map(|&n| (n, self.tiles[n].center.dot(direction)))
+                .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+            match closer {
+                Some((n, d)) if d > best => {
+                    tile = n;
+                    best = d;
+                }
+                _ => return tile,
+            }
+        }
+    }
+
+    pub fn tile_at(&self, direction: Vec3) -> usize {
+        self.tile_at_from(direction, 0)
+    }
+
+    /// Builds the tiles as a triangle fan each, colored with `Tile::color`. `height` is the
+    /// distance from the planet center at a tile's center, corners take the average of the
+    /// three tiles meeting there so the surface stays closed.
+    ///
+    /// Every tile gets its own vertices so its colour doesn't bleed, that's roughly seven
+    /// vertices per tile.
+    pub fn mesh(&self, height: impl Fn(Vec3) -> f32) -> Mesh {
+        let tile_heights = self
+            .tiles
+            .iter()
+            .map(|tile| height(tile.center))
+            .collect::<Vec<_>>();
+        let corner_points = self
+            .corners
+            .iter()
+            .zip(self.corner_tiles.iter())
+            .map(|(corner, tiles)| {
+                *corner * (tiles.iter().map(|t| tile_heights[*t]).sum::<f32>() / 3.0)
+            })
+            .collect::<Vec<_>>();
+
+        let vertex_count = self
+            .tiles
+            .iter()
+            .map(|t| t.corners.len() + 1)
+            .sum::<usize>();
+        let mut positions = Vec::with_capacity(vertex_count);
+        let mut normals = Vec::with_capacity(vertex_count);
+        let mut uvs = Vec::with_capacity(vertex_count);
+        let mut colors = Vec::with_capacity(vertex_count);
+        let mut indices = Vec::new();
+        for (tile, tile_height) in self.tiles.iter().zip(tile_heights.iter()) {
+            let first = positions.len() as u32;
+            let points = std::iter::once(tile.center * *tile_height)
+                .chain(tile.corners.iter().map(|c| corner_points[*c]));
+            for point in points {
+                let (latitude, longitude) = direction_to_lat_long(point);
+                positions.push(point.into());
+                // the tile's own up, so each tile reads as one flat cell
+                normals.push(tile.center.into());
+                uvs.push([
+                    longitude / (2.0 * std::f32::consts::PI) + 0.5,
+                    0.5 - latitude / std::f32::consts::PI,
+                ]);
+                colors.push([tile.color.r, tile.color.g, tile.color.b, tile.color.a]);
+            }
+            let sides = tile.corners.len() as u32;
+            for i in 0..sides {
+                indices.extend_from_slice(&[first, first + 1 + i, first + 1 + (i + 1) % sides]);
+            }
+        }
+
+        Mesh {
+            primitive_topology: PrimitiveTopology::TriangleList,
+            attributes: vec![
+                VertexAttribute::position(positions),
+                VertexAttribute::normal(normals),
+                VertexAttribute::uv(uvs),
+                VertexAttribute::color(colors),
+            ],
+            indices: Some(indices),
+            dirty_vertices: Default::default(),
+            bounds: Default::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::HexGrid;
+
+    #[test]
+    fn test_tile_shapes() {
+        let grid = HexGrid::new(4);
+        let pentagons = grid.tiles.iter().filter(|t| t.corners.len() == 5).count();
+        assert_eq!(pentagons, 12);
+        for tile in grid.tiles.iter() {
+            assert!(tile.corners.len() == 5 || tile.corners.len() == 6);
+            assert_eq!(tile.corners.len(), tile.neighbours.len());
+        }
+    }
+
+    #[test]
+    fn test_neighbours_are_symmetric() {
+        let grid = HexGrid::new(4);
+        for (id, tile) in grid.tiles.iter().enumerate() {
+            for &neighbour in tile.neighbours.iter() {
+                assert_ne!(neighbour, id);
+                assert!(
+                    grid.neighbours(neighbour).contains(&id),
+                    "{} lists {} but not the other way around",
+                    id,
+                    neighbour
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_tile_at_center() {
+        let grid = HexGrid::new(4);
+        for (id, tile) in grid.tiles.iter().enumerate() {
+            assert_eq!(grid.tile_at(tile.center), id);
+        }
+    }
+}
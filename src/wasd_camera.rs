@@ -65,7 +65,7 @@ impl Plugin for WasdCamera {
 #[derive(Default)]
 pub struct CursorListener {
     cursor_event: EventReader<CursorMoved>,
-    pos: Vec2,
+    pub pos: Vec2,
 }
 #[derive(Default)]
 struct Momentum {